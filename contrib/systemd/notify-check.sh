@@ -0,0 +1,19 @@
+#!/bin/sh
+# Run kyotod in the foreground against a stand-in notify socket and print
+# every sd_notify message it sends (READY=1, STATUS=..., WATCHDOG=1,
+# STOPPING=1). Needs socat. Arguments are passed through to kyotod:
+#
+#   contrib/systemd/notify-check.sh --datadir /tmp/kyotod-test --network signet
+set -eu
+
+dir=$(mktemp -d)
+sock="$dir/notify.sock"
+
+socat -u UNIX-RECV:"$sock" STDOUT &
+listener=$!
+trap 'kill "$listener" 2>/dev/null; rm -rf "$dir"' EXIT INT TERM
+sleep 0.2
+
+# WATCHDOG_PID must name kyotod itself, hence the exec from a child shell.
+NOTIFY_SOCKET="$sock" WATCHDOG_USEC=20000000 sh -c \
+    'WATCHDOG_PID=$$ exec kyotod --daemon false "$@"' kyotod "$@"
@@ -0,0 +1,160 @@
+use std::ffi::{CStr, CString};
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{chown, DirBuilderExt, PermissionsExt};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+
+use bdk_kyoto::bip157::tokio;
+use tokio::net::unix::UCred;
+
+const OWNER_ONLY: u32 = 0o600;
+const OWNER_AND_GROUP: u32 = 0o660;
+
+// What `getgrouplist` takes and fills in.
+#[cfg(target_os = "macos")]
+type GroupId = libc::c_int;
+#[cfg(not(target_os = "macos"))]
+type GroupId = libc::gid_t;
+
+/// Who may talk to the IPC socket. The daemon's own uid is always allowed.
+/// Everyone else must be listed by uid, or belong (primary or supplementary)
+/// to a listed gid or the socket's group.
+pub struct SocketAccess {
+    group: Option<u32>,
+    allow_uids: Vec<u32>,
+    allow_gids: Vec<u32>,
+}
+
+impl SocketAccess {
+    pub fn new(
+        group: Option<&str>,
+        allow_uids: Option<&str>,
+        allow_gids: Option<&str>,
+    ) -> Result<Self, String> {
+        let group = group.map(resolve_group).transpose()?;
+        let allow_uids = parse_ids(allow_uids.unwrap_or(""), "uid")?;
+        let mut allow_gids = parse_ids(allow_gids.unwrap_or(""), "gid")?;
+        allow_gids.extend(group);
+        Ok(Self {
+            group,
+            allow_uids,
+            allow_gids,
+        })
+    }
+
+    /// Bind the socket at `path` with its final group and mode already set.
+    /// It is created in a private directory beside `path` and only then
+    /// renamed into place, so it is never reachable with the process umask's
+    /// permissions.
+    pub fn bind(&self, path: &Path) -> io::Result<UnixListener> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::other("socket path has no file name"))?;
+        let private = path.with_file_name(format!(
+            ".{}.{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        if private.exists() {
+            fs::remove_dir_all(&private)?;
+        }
+        DirBuilder::new().mode(0o700).create(&private)?;
+        let staged = private.join(name);
+        let bound = UnixListener::bind(&staged)
+            .and_then(|listener| self.apply(&staged).map(|()| listener))
+            .and_then(|listener| fs::rename(&staged, path).map(|()| listener));
+        let _ = fs::remove_dir_all(&private);
+        bound
+    }
+
+    /// Restrict a socket to its owner, or owner and group when a socket
+    /// group is configured.
+    fn apply(&self, path: &Path) -> io::Result<()> {
+        let mode = match self.group {
+            Some(gid) => {
+                chown(path, None, Some(gid))?;
+                OWNER_AND_GROUP
+            }
+            None => OWNER_ONLY,
+        };
+        fs::set_permissions(path, Permissions::from_mode(mode))
+    }
+
+    pub fn permits(&self, cred: &UCred) -> bool {
+        let uid = cred.uid();
+        if uid == unsafe { libc::geteuid() } || self.allow_uids.contains(&uid) {
+            return true;
+        }
+        if self.allow_gids.is_empty() {
+            return false;
+        }
+        user_groups(uid, cred.gid())
+            .iter()
+            .any(|g| self.allow_gids.contains(g))
+    }
+}
+
+fn parse_ids(list: &str, what: &str) -> Result<Vec<u32>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|e| format!("{what} '{s}': {e}")))
+        .collect()
+}
+
+fn resolve_group(name: &str) -> Result<u32, String> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let cname = CString::new(name).map_err(|e| format!("group '{name}': {e}"))?;
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getgrnam_r(
+            cname.as_ptr(),
+            &mut grp,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() {
+        return Err(format!("unknown group '{name}'"));
+    }
+    Ok(grp.gr_gid)
+}
+
+// Primary plus supplementary groups of `uid`. SO_PEERCRED only reports the
+// primary gid, which would lock out users added to the socket group the
+// usual way.
+#[allow(clippy::unnecessary_cast)]
+fn user_groups(uid: u32, primary: u32) -> Vec<u32> {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return vec![primary];
+    }
+    let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+    let mut count: libc::c_int = 64;
+    let mut groups: Vec<GroupId> = vec![0; count as usize];
+    for _ in 0..2 {
+        let rc = unsafe {
+            libc::getgrouplist(
+                name.as_ptr(),
+                primary as GroupId,
+                groups.as_mut_ptr(),
+                &mut count,
+            )
+        };
+        if rc != -1 {
+            groups.truncate(count as usize);
+            return groups.into_iter().map(|g| g as u32).collect();
+        }
+        groups.resize(count as usize, 0);
+    }
+    vec![primary]
+}
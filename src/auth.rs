@@ -0,0 +1,97 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use bdk_wallet::bitcoin::hex::DisplayHex;
+
+use crate::paths::Layout;
+use crate::random;
+
+const TOKEN_BYTES: usize = 32;
+
+/// What an IPC capability may do. Every client starts at `ReadOnly` and can
+/// trade a cookie for a `Spender` or `Admin` capability via `authenticate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    ReadOnly,
+    Spender,
+    Admin,
+}
+
+impl std::fmt::Display for Tier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ReadOnly => "read-only",
+            Self::Spender => "spender",
+            Self::Admin => "admin",
+        })
+    }
+}
+
+impl std::str::FromStr for Tier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" => Ok(Self::ReadOnly),
+            "spender" => Ok(Self::Spender),
+            "admin" => Ok(Self::Admin),
+            other => Err(format!(
+                "unknown tier `{other}` (read-only, spender, admin)"
+            )),
+        }
+    }
+}
+
+/// Random per-run tokens, written to `spender.cookie` and `admin.cookie` in
+/// the datadir (mode 0600), in the spirit of Bitcoin Core's `.cookie`.
+pub struct Cookies {
+    spender: String,
+    admin: String,
+}
+
+impl Cookies {
+    pub fn generate(layout: &Layout) -> io::Result<Self> {
+        let cookies = Self {
+            spender: random_token()?,
+            admin: random_token()?,
+        };
+        write_cookie(&layout.spender_cookie(), &cookies.spender)?;
+        write_cookie(&layout.admin_cookie(), &cookies.admin)?;
+        Ok(cookies)
+    }
+
+    pub fn check(&self, token: &str) -> Option<Tier> {
+        if constant_time_eq(token.as_bytes(), self.admin.as_bytes()) {
+            Some(Tier::Admin)
+        } else if constant_time_eq(token.as_bytes(), self.spender.as_bytes()) {
+            Some(Tier::Spender)
+        } else {
+            None
+        }
+    }
+
+    pub fn remove(layout: &Layout) {
+        let _ = fs::remove_file(layout.spender_cookie());
+        let _ = fs::remove_file(layout.admin_cookie());
+    }
+}
+
+fn random_token() -> io::Result<String> {
+    Ok(random::bytes::<TOKEN_BYTES>()?.to_lower_hex_string())
+}
+
+fn write_cookie(path: &Path, token: &str) -> io::Result<()> {
+    let _ = fs::remove_file(path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
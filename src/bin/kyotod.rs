@@ -1,36 +1,198 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::os::fd::OwnedFd;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use bdk_kyoto::bip157::tokio;
 use bdk_kyoto::{HashCheckpoint, Requester, ScanType};
 use bdk_wallet::bitcoin::Network;
+use kyotod::access::SocketAccess;
+use kyotod::auth::{Cookies, Tier};
+use kyotod::broadcast::{self, Broadcaster, Route};
+use kyotod::chain::{Chain, Chains};
+use kyotod::checkpoints::{CheckpointFile, Checkpoints};
+use kyotod::control::{self, Cmdline};
 use kyotod::daemonize::Daemonize;
-use kyotod::ipc::{self, RequesterSlot, ServerArgs};
+use kyotod::fees::BlockFees;
+use kyotod::hooks::{self, Hooks};
+use kyotod::ipc::{self, ReloadReport, ReloadRequest, ServerArgs};
+use kyotod::lock::{read_pid, DatadirLock};
+use kyotod::logbuf::{LogBuffer, LogRing};
+use kyotod::logging::{self, LogControl, LogFile, Rotation};
+use kyotod::metrics::{self, Metrics, SharedMetrics};
 use kyotod::paths::Layout;
-use kyotod::sync::{self, ProgressSlot, RequiredPeers, SyncHandle, TorProxy, TrustedPeers};
+use kyotod::rebroadcast::{self, Pending};
+use kyotod::remote::{self, ConnectString};
+use kyotod::reorg::Reorgs;
+use kyotod::sync::{self, Health, RequiredPeers, SyncDetail, SyncHandle, SyncHealth, TorProxy};
+use kyotod::systemd::{self, Notifier};
 use kyotod::wallet::State;
+use kyotod::watch;
+use kyotod::webhooks::{self, Outbox};
 use tokio::signal::unix::{signal, SignalKind};
 use tracing::{info, warn};
 
 configure_me::include_config!();
 
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(5);
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(300);
+/// A restarted light client that stays up this long clears the degraded
+/// state and resets the backoff.
+const RESTART_STABLE: Duration = Duration::from_secs(120);
+
 fn main() {
-    let (config, _) =
-        Config::including_optional_config_files::<&[&str]>(&[]).unwrap_or_exit();
+    // The config file lives in the datadir, but the datadir can itself be set
+    // on the command line or in the environment, so look there first.
+    let conf = kyotod::paths::expand(&datadir_hint()).join("kyotod.conf");
+    let (config, mut args) = Config::including_optional_config_files([&conf]).unwrap_or_exit();
+
+    let (log, logs) = match init_logging(&config) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("error: logging: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let networks = match configured_networks(&config) {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let mut layout = Layout::new(&config.datadir).expect("failed to prepare data directory");
+    if let Some(socket) = config.socket.as_deref() {
+        layout = layout.with_socket(socket);
+    }
+    let layout = Arc::new(layout);
+
+    let remote_listen = match config.remote_listen.as_deref().map(SocketAddr::from_str) {
+        None => None,
+        Some(Ok(addr)) => Some(addr),
+        Some(Err(e)) => {
+            eprintln!("error: remote_listen: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = webhook_settings(&config) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    if let Err(e) = broadcast_policy(&config, true) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    if let Err(e) = rebroadcast_policy(&config) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    // The endpoint has no authentication, so keep it off the network.
+    let metrics_listen = match config.metrics_listen.as_deref().map(SocketAddr::from_str) {
+        None => None,
+        Some(Ok(addr)) if addr.ip().is_loopback() => Some(addr),
+        Some(Ok(addr)) => {
+            eprintln!("error: metrics_listen: {addr} is not a loopback address");
+            std::process::exit(1);
+        }
+        Some(Err(e)) => {
+            eprintln!("error: metrics_listen: {e}");
+            std::process::exit(1);
+        }
+    };
 
-    tracing_subscriber::fmt()
-        .with_target(true)
-        .with_level(true)
-        .init();
+    match args
+        .next()
+        .map(|a| a.to_string_lossy().into_owned())
+        .as_deref()
+    {
+        None => {}
+        Some("pair") => {
+            let key = args.next().map(|a| a.to_string_lossy().into_owned());
+            let tier = args.next().map(|a| a.to_string_lossy().into_owned());
+            match pair(
+                &config,
+                &layout,
+                remote_listen,
+                key.as_deref(),
+                tier.as_deref(),
+            ) {
+                Ok(s) => {
+                    println!("{s}");
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("error: pair: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(cmd @ ("status" | "stop" | "restart")) => {
+            let timeout = match args.next().map(|a| a.to_string_lossy().parse::<u64>()) {
+                None => DEFAULT_STOP_TIMEOUT,
+                Some(Ok(secs)) => Duration::from_secs(secs),
+                Some(Err(e)) => {
+                    eprintln!("error: timeout: {e}");
+                    std::process::exit(2);
+                }
+            };
+            std::process::exit(manage(cmd, &layout, timeout));
+        }
+        Some(other) => {
+            eprintln!("error: unknown command '{other}'");
+            std::process::exit(2);
+        }
+    }
 
-    let network = Network::from_str(&config.network).expect("invalid network");
-    let layout = Arc::new(Layout::new(&config.datadir).expect("failed to prepare data directory"));
+    // Held until the process exits; run() never returns.
+    let lock = match DatadirLock::acquire(&layout) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("error: {}: {e}", layout.root.display());
+            std::process::exit(1);
+        }
+    };
+    let listen_fd = match systemd::take_listen_fd() {
+        Ok(fd) => fd,
+        Err(e) => {
+            eprintln!("error: socket activation: {e}");
+            std::process::exit(1);
+        }
+    };
+    let cleared = if listen_fd.is_some() {
+        lock.clear_stale_pid(&layout)
+    } else {
+        lock.clear_stale(&layout)
+    };
+    if let Err(e) = cleared {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    // Saved before the fork changes the working directory.
+    if let Err(e) = Cmdline::current().and_then(|c| c.save(&layout.cmdline_file())) {
+        warn!(target: "node", "saving command line for restart: {e}");
+    }
+    let access = match SocketAccess::new(
+        config.socket_group.as_deref(),
+        config.allow_uids.as_deref(),
+        config.allow_gids.as_deref(),
+    ) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("error: socket access: {e}");
+            std::process::exit(1);
+        }
+    };
 
     info!(
         target: "node",
         "kyotod starting: network={} datadir={}",
-        config.network,
+        networks.iter().map(ToString::to_string).collect::<Vec<_>>().join(","),
         layout.root.display()
     );
 
@@ -45,123 +207,1080 @@ fn main() {
             .expect("datadir is not valid UTF-8")
             .to_owned();
         Daemonize::new(working_dir)
+            .log_file(log_path(&config))
             .fork()
             .expect("failed to daemonize");
+    } else if let Err(e) = std::fs::write(layout.pid_file(), std::process::id().to_string()) {
+        warn!(target: "node", "writing {}: {e}", layout.pid_file().display());
     }
 
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("tokio runtime");
-    rt.block_on(run(config, network, layout));
+    rt.block_on(run(
+        config,
+        networks,
+        layout,
+        access,
+        remote_listen,
+        metrics_listen,
+        listen_fd,
+        log,
+        logs,
+    ));
 }
 
-async fn run(config: Config, network: Network, layout: Arc<Layout>) {
-    let state = Arc::new(Mutex::new(
-        State::load(&layout, network).expect("failed to load wallets"),
-    ));
-    {
-        let s = state.lock().unwrap();
-        info!(
-            target: "wallet",
-            "loaded {} wallet(s); active={:?}",
-            s.wallets.len(),
-            s.active
+/// Run a management subcommand against the daemon that owns `layout` and
+/// return the process exit code. `status` exits 3 when nothing is running,
+/// as LSB init scripts expect.
+fn manage(cmd: &str, layout: &Layout, timeout: Duration) -> i32 {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    rt.block_on(tokio::task::LocalSet::new().run_until(async {
+        match cmd {
+            "status" => {
+                if let Ok(client) = control::connect(&layout.socket()).await {
+                    match control::status(&client).await {
+                        Ok(s) => {
+                            println!("{s}");
+                            return 0;
+                        }
+                        Err(e) => eprintln!("warning: status: {e}"),
+                    }
+                }
+                match control::is_running(layout) {
+                    Ok(true) => {
+                        println!(
+                            "kyotod is running (pid {}) but not answering on {}",
+                            read_pid(&layout.pid_file()).map_or("?".into(), |p| p.to_string()),
+                            layout.socket().display()
+                        );
+                        1
+                    }
+                    Ok(false) => {
+                        println!("kyotod is not running");
+                        3
+                    }
+                    Err(e) => {
+                        eprintln!("error: {e}");
+                        1
+                    }
+                }
+            }
+            "stop" => match control::stop(layout, timeout).await {
+                Ok(how) => {
+                    if let control::Stopped::Signalled(Some(e)) = how {
+                        eprintln!(
+                            "warning: shutdown over the socket failed ({e}); signalled instead"
+                        );
+                    }
+                    println!("kyotod stopped");
+                    0
+                }
+                Err(e) => {
+                    eprintln!("error: stop: {e}");
+                    1
+                }
+            },
+            _ => match control::restart(layout, timeout).await {
+                Ok(s) => {
+                    println!("{s}");
+                    0
+                }
+                Err(e) => {
+                    eprintln!("error: restart: {e}");
+                    1
+                }
+            },
+        }
+    }))
+}
+
+/// Authorize a remote client's public key at `tier` (read-only unless given)
+/// and return the connection string it should use.
+fn pair(
+    config: &Config,
+    layout: &Layout,
+    listen: Option<SocketAddr>,
+    key: Option<&str>,
+    tier: Option<&str>,
+) -> Result<String, String> {
+    let listen = listen.ok_or("remote_listen is not set")?;
+    let key = key
+        .ok_or("usage: kyotod pair <client-key> [tier]")?
+        .parse::<remote::PublicKey>()?;
+    let tier = tier
+        .map(Tier::from_str)
+        .transpose()?
+        .unwrap_or(Tier::ReadOnly);
+    let addr = match config.remote_host.as_deref() {
+        Some(host) => format!("{host}:{}", listen.port()),
+        None if listen.ip().is_unspecified() => {
+            return Err(format!(
+                "remote_listen is {listen}; set remote_host to the address clients should dial"
+            ))
+        }
+        None => listen.to_string(),
+    };
+    let server =
+        remote::Keypair::load_or_create(&layout.remote_key()).map_err(|e| e.to_string())?;
+    remote::authorize_client(&layout.remote_clients(), &key, tier).map_err(|e| e.to_string())?;
+    eprintln!("paired {key} as a {tier} client; connect with:");
+    Ok(ConnectString {
+        addr,
+        server: server.public,
+    }
+    .to_string())
+}
+
+async fn run(
+    config: Config,
+    networks: Vec<Network>,
+    layout: Arc<Layout>,
+    access: SocketAccess,
+    remote_listen: Option<SocketAddr>,
+    metrics_listen: Option<SocketAddr>,
+    listen_fd: Option<OwnedFd>,
+    log: LogControl,
+    logs: LogBuffer,
+) {
+    let notifier = Notifier::from_env();
+    let socket_activated = listen_fd.is_some();
+    let required_peers: RequiredPeers = Arc::new(Mutex::new(config.required_peers.clamp(1, 15)));
+    let tor_proxy: TorProxy = Arc::new(Mutex::new(configured_proxy(&config)));
+    let hooks = Hooks::spawn(hook_settings(&config));
+    let metrics = Metrics::new();
+    let chains: Chains = Arc::new(
+        networks
+            .iter()
+            .enumerate()
+            .map(|(i, &network)| {
+                open_chain(
+                    &config,
+                    &layout,
+                    network,
+                    i == 0,
+                    &hooks,
+                    &tor_proxy,
+                    &metrics,
+                )
+            })
+            .collect(),
+    );
+    if config.broadcast_policy != "node" && tor_proxy.lock().unwrap().is_none() {
+        warn!(
+            target: "node",
+            "broadcast_policy {}: no proxy is set, so broadcast peers see this host's address",
+            config.broadcast_policy
         );
     }
 
-    let progress: ProgressSlot = Arc::new(Mutex::new(None));
-    let required_peers: RequiredPeers = Arc::new(Mutex::new(1));
-    let trusted_peers: TrustedPeers = Arc::new(Mutex::new(Vec::new()));
-    let tor_proxy: TorProxy = Arc::new(Mutex::new(None));
-    let mut handle: Option<SyncHandle> = if state.lock().unwrap().wallets.is_empty() {
-        info!(target: "node", "no wallets present; waiting for import");
-        None
-    } else {
-        Some(sync::spawn(
-            network,
-            state.clone(),
-            HashMap::new(),
-            progress.clone(),
-            required_peers.clone(),
-            trusted_peers.clone(),
-            tor_proxy.clone(),
-        ))
+    let mut checkpoints: Vec<Checkpoints> =
+        networks.iter().map(|&n| Checkpoints::builtin(n)).collect();
+    if let Some(path) = config.checkpoints.as_deref() {
+        let path = kyotod::paths::expand(path);
+        match CheckpointFile::load(&path) {
+            Ok(file) => {
+                for (i, (cps, &network)) in checkpoints.iter_mut().zip(&networks).enumerate() {
+                    let n = cps.merge(&file, network, i == 0);
+                    info!(target: "node", "{network}: loaded {n} checkpoint(s) from {}", path.display());
+                }
+            }
+            Err(e) => {
+                warn!(target: "node", "checkpoints {}: {e}; using built-ins only", path.display())
+            }
+        }
+    }
+    for (cps, network) in checkpoints.iter().zip(&networks) {
+        info!(target: "node", "{network}: {} header checkpoint(s) available", cps.len());
+    }
+
+    let mut handles: Vec<Option<SyncHandle>> = Vec::new();
+    for (chain, checkpoints) in chains.iter().zip(&checkpoints) {
+        tokio::spawn(webhooks::deliver_loop(chain.webhooks.clone()));
+        handles.push(if chain.state.lock().unwrap().wallets.is_empty() {
+            info!(target: "node", "{}: no wallets present; waiting for import", chain.network);
+            None
+        } else {
+            let scans = resolve_scans(&chain.state, None, checkpoints).await;
+            Some(start_sync(chain, scans, &required_peers, &tor_proxy))
+        });
+    }
+    let cookies = Arc::new(Cookies::generate(&layout).expect("failed to write cookie files"));
+    for chain in chains.iter() {
+        tokio::spawn(rebroadcast::rebroadcast_loop(
+            chain.pending.clone(),
+            chain.state.clone(),
+            chain.broadcaster.clone(),
+        ));
+        if config.peer_tip_check {
+            tokio::spawn(sync::peer_tip_loop(
+                chain.network,
+                chain.requester.clone(),
+                chain.details.clone(),
+                chain.trusted_peers.clone(),
+                tor_proxy.clone(),
+            ));
+        }
+    }
+    if let Some(addr) = metrics_listen {
+        tokio::spawn(metrics::serve(
+            addr,
+            metrics::Sources {
+                metrics: metrics.clone(),
+                chains: chains
+                    .iter()
+                    .map(|chain| metrics::ChainSources {
+                        network: chain.network,
+                        state: chain.state.clone(),
+                        requester: chain.requester.clone(),
+                        progress: chain.progress.clone(),
+                    })
+                    .collect(),
+                required_peers: required_peers.clone(),
+                balances: config.metrics_wallet_balances,
+            },
+        ));
+    }
+
+    let watch_wallets = config.watch_wallets;
+    let mut reloader = Reloader {
+        log: log.clone(),
+        chains: chains.clone(),
+        required_peers: required_peers.clone(),
+        tor_proxy: tor_proxy.clone(),
+        config,
     };
-    let requester_slot: RequesterSlot =
-        Arc::new(Mutex::new(handle.as_ref().map(|h| h.requester.clone())));
 
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
-    let (rebuild_tx, mut rebuild_rx) = tokio::sync::mpsc::channel::<()>(1);
-    ipc::spawn_server(ServerArgs {
+    // Room for the main loop to queue every network at once.
+    let (rebuild_tx, mut rebuild_rx) = tokio::sync::mpsc::channel::<Network>(chains.len());
+    let (reload_tx, mut reload_rx) = tokio::sync::mpsc::channel::<ReloadRequest>(1);
+    if watch_wallets {
+        for chain in chains.iter() {
+            tokio::spawn(watch::watch_wallets(
+                chain.layout.clone(),
+                chain.network,
+                chain.state.clone(),
+                rebuild_tx.clone(),
+            ));
+        }
+    }
+    let bound = ipc::spawn_server(ServerArgs {
         layout: layout.clone(),
-        network,
         shutdown_tx,
-        rebuild_tx,
-        state: state.clone(),
-        requester: requester_slot.clone(),
-        progress: progress.clone(),
+        rebuild_tx: rebuild_tx.clone(),
+        reload_tx,
+        chains: chains.clone(),
         required_peers: required_peers.clone(),
-        trusted_peers: trusted_peers.clone(),
         tor_proxy: tor_proxy.clone(),
+        log: log.clone(),
+        logs,
+        metrics: metrics.clone(),
+        access,
+        cookies,
+        remote_listen,
+        started: Instant::now(),
+        listen_fd,
     });
+    if bound.await.is_ok() {
+        notifier.ready();
+    }
+    let mut ticker = tokio::time::interval(
+        notifier
+            .watchdog_interval()
+            .map_or(STATUS_INTERVAL, |w| w.min(STATUS_INTERVAL)),
+    );
+    let mut last_status = String::new();
+    let mut supervise = tokio::time::interval(SUPERVISE_INTERVAL);
+    let mut supervisors: Vec<Supervisor> = networks.iter().map(|&n| Supervisor::new(n)).collect();
 
     let mut sigint = signal(SignalKind::interrupt()).expect("register SIGINT handler");
     let mut sigterm = signal(SignalKind::terminate()).expect("register SIGTERM handler");
+    let mut sighup = signal(SignalKind::hangup()).expect("register SIGHUP handler");
     let cause = loop {
         tokio::select! {
             _ = shutdown_rx.recv() => break "ipc",
             _ = sigint.recv() => break "SIGINT",
             _ = sigterm.recv() => break "SIGTERM",
-            Some(_) = rebuild_rx.recv() => {
-                info!(target: "node", "rebuilding light client");
-                let scans = resolve_scans(&state, handle.as_ref().map(|h| &h.requester)).await;
-                *requester_slot.lock().unwrap() = None;
-                *progress.lock().unwrap() = None;
-                if let Some(h) = handle.take() {
-                    sync::shutdown(h).await;
-                }
-                let h = sync::spawn(
-                    network,
-                    state.clone(),
-                    scans,
-                    progress.clone(),
-                    required_peers.clone(),
-                    trusted_peers.clone(),
-                    tor_proxy.clone(),
-                );
-                *requester_slot.lock().unwrap() = Some(h.requester.clone());
-                handle = Some(h);
-                info!(target: "node", "light client rebuilt");
+            _ = sighup.recv() => {
+                info!(target: "node", "SIGHUP: reloading configuration");
+                let (_, rebuild) = reloader.reload(&mut checkpoints);
+                for network in rebuild {
+                    let _ = rebuild_tx.try_send(network);
+                }
+            }
+            Some(reply) = reload_rx.recv() => {
+                info!(target: "node", "reloading configuration (ipc)");
+                let (report, rebuild) = reloader.reload(&mut checkpoints);
+                for network in rebuild {
+                    let _ = rebuild_tx.try_send(network);
+                }
+                let _ = reply.send(report);
+            }
+            _ = ticker.tick(), if notifier.enabled() => {
+                if notifier.watchdog_interval().is_some() {
+                    notifier.watchdog();
+                }
+                let status = service_status(&chains, &handles);
+                if status != last_status {
+                    notifier.status(&status);
+                    last_status = status;
+                }
+            }
+            _ = supervise.tick() => {
+                if let Some(e) = log.take_rotation_error() {
+                    warn!(target: "node", "{e}");
+                }
+                for ((chain, handle), supervisor) in chains.iter().zip(&handles).zip(&mut supervisors) {
+                    if supervisor.check(handle.as_ref(), &chain.health) {
+                        let _ = rebuild_tx.try_send(chain.network);
+                    }
+                }
+            }
+            Some(network) = rebuild_rx.recv() => {
+                // Requests queued behind this one are served by the same rebuild.
+                let mut due = vec![network];
+                while let Ok(n) = rebuild_rx.try_recv() {
+                    if !due.contains(&n) {
+                        due.push(n);
+                    }
+                }
+                for network in due {
+                    let Some(i) = chains.iter().position(|c| c.network == network) else {
+                        continue;
+                    };
+                    let chain = &chains[i];
+                    info!(target: "node", "{network}: rebuilding light client");
+                    let scans = resolve_scans(&chain.state, handles[i].as_ref().map(|h| &h.requester), &checkpoints[i]).await;
+                    *chain.requester.lock().unwrap() = None;
+                    *chain.progress.lock().unwrap() = None;
+                    chain.details.lock().unwrap().restart();
+                    if let Some(h) = handles[i].take() {
+                        sync::shutdown(h).await;
+                    }
+                    handles[i] = Some(start_sync(chain, scans, &required_peers, &tor_proxy));
+                    supervisors[i].rebuilt();
+                    metrics.rebuilt(network);
+                    info!(target: "node", "{network}: light client rebuilt");
+                }
             }
         }
     };
     info!(target: "node", "shutting down ({cause})");
+    notifier.stopping();
 
-    if let Some(h) = handle {
+    for h in handles.into_iter().flatten() {
         sync::shutdown(h).await;
     }
+    // Under socket activation the service manager owns the socket and keeps
+    // listening on it.
     let sock = layout.socket();
-    if let Err(e) = std::fs::remove_file(&sock) {
-        if e.kind() != std::io::ErrorKind::NotFound {
-            warn!(target: "node", "remove {}: {e}", sock.display());
+    if !socket_activated {
+        if let Err(e) = std::fs::remove_file(&sock) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(target: "node", "remove {}: {e}", sock.display());
+            }
         }
     }
-    if config.daemon {
-        let pid = layout.root.join("node.pid");
-        let _ = std::fs::remove_file(pid);
-    }
+    Cookies::remove(&layout);
+    let _ = std::fs::remove_file(layout.pid_file());
     std::process::exit(0);
 }
 
+/// Load a network's wallets and open its queues. The primary network keeps
+/// the top of the datadir, and is the only one the trusted peers and the
+/// broadcast peer are for.
+fn open_chain(
+    config: &Config,
+    layout: &Arc<Layout>,
+    network: Network,
+    primary: bool,
+    hooks: &Hooks,
+    tor_proxy: &TorProxy,
+    metrics: &SharedMetrics,
+) -> Chain {
+    let layout = if primary {
+        layout.clone()
+    } else {
+        Arc::new(
+            layout
+                .for_network(network)
+                .expect("failed to prepare network directory"),
+        )
+    };
+    let state = State::load(&layout, network).expect("failed to load wallets");
+    info!(
+        target: "wallet",
+        "{network}: loaded {} wallet(s); active={:?}",
+        state.wallets.len(),
+        state.active
+    );
+    let pending = Pending::open(
+        &layout.pending_db(),
+        rebroadcast_policy(config).expect("checked at startup"),
+    )
+    .expect("failed to open pending broadcast queue");
+    if !pending.is_empty() {
+        info!(
+            target: "node",
+            "{network}: {} transaction(s) tracked for rebroadcast",
+            pending.len()
+        );
+    }
+    let webhooks = Outbox::open(
+        &layout.webhooks_db(),
+        webhook_settings(config).expect("checked at startup"),
+    )
+    .expect("failed to open webhook outbox");
+    if !webhooks.is_empty() {
+        info!(target: "wallet", "{network}: {} webhook(s) waiting for delivery", webhooks.len());
+    }
+    let trusted = if primary {
+        configured_peers(config, network)
+    } else {
+        Vec::new()
+    };
+    let requester = Arc::new(Mutex::new(None));
+    let broadcaster = Broadcaster::new(
+        network,
+        broadcast_policy(config, primary).expect("checked at startup"),
+        requester.clone(),
+        tor_proxy.clone(),
+        metrics.clone(),
+    );
+    Chain {
+        network,
+        layout,
+        state: Arc::new(Mutex::new(state)),
+        requester,
+        progress: Arc::new(Mutex::new(None)),
+        details: Arc::new(Mutex::new(SyncDetail::default())),
+        health: Arc::new(Mutex::new(Health::default())),
+        trusted_peers: Arc::new(Mutex::new(trusted)),
+        fees: Arc::new(Mutex::new(BlockFees::new(config.fee_blocks))),
+        pending: Arc::new(Mutex::new(pending)),
+        hooks: if primary {
+            hooks.clone()
+        } else {
+            hooks.for_network()
+        },
+        webhooks: Arc::new(Mutex::new(webhooks)),
+        reorgs: Reorgs::new(),
+        broadcaster,
+    }
+}
+
+/// Start a network's light client and publish its requester.
+fn start_sync(
+    chain: &Chain,
+    scans: HashMap<String, ScanType>,
+    required_peers: &RequiredPeers,
+    tor_proxy: &TorProxy,
+) -> SyncHandle {
+    let h = sync::spawn(
+        chain.network,
+        chain.state.clone(),
+        scans,
+        chain.progress.clone(),
+        chain.details.clone(),
+        required_peers.clone(),
+        chain.trusted_peers.clone(),
+        tor_proxy.clone(),
+        chain.fees.clone(),
+        chain.hooks.clone(),
+        chain.webhooks.clone(),
+        chain.reorgs.clone(),
+    );
+    *chain.requester.lock().unwrap() = Some(h.requester.clone());
+    h
+}
+
+/// Restarts a network's light client through the rebuild path when one of
+/// its tasks dies, backing off exponentially while restarts keep failing.
+struct Supervisor {
+    network: Network,
+    // Failures since the light client last stayed up for RESTART_STABLE.
+    failures: u32,
+    retry_at: Option<Instant>,
+    rebuilt_at: Option<Instant>,
+}
+
+impl Supervisor {
+    fn new(network: Network) -> Self {
+        Self {
+            network,
+            failures: 0,
+            retry_at: None,
+            rebuilt_at: None,
+        }
+    }
+
+    /// Returns whether a restart is due now.
+    fn check(&mut self, handle: Option<&SyncHandle>, health: &SyncHealth) -> bool {
+        let network = self.network;
+        let now = Instant::now();
+        if let Some(at) = self.retry_at {
+            if now < at {
+                return false;
+            }
+            self.retry_at = None;
+            health.lock().unwrap().restarts += 1;
+            info!(target: "node", "{network}: restarting light client (attempt {})", self.failures);
+            return true;
+        }
+        if let Some(reason) = handle.and_then(SyncHandle::failure) {
+            let delay = RESTART_BASE_DELAY
+                .saturating_mul(1 << self.failures.min(10))
+                .min(RESTART_MAX_DELAY);
+            self.failures += 1;
+            self.rebuilt_at = None;
+            self.retry_at = Some(now + delay);
+            warn!(
+                target: "node",
+                "{network}: {reason}; restarting light client in {}s",
+                delay.as_secs()
+            );
+            health.lock().unwrap().degraded = Some(reason.to_string());
+            return false;
+        }
+        if self
+            .rebuilt_at
+            .is_some_and(|t| now.duration_since(t) >= RESTART_STABLE)
+        {
+            self.rebuilt_at = None;
+            self.failures = 0;
+            if health.lock().unwrap().degraded.take().is_some() {
+                info!(target: "node", "{network}: light client recovered");
+            }
+        }
+        false
+    }
+
+    /// Any rebuild, requested or not, replaces a failed light client.
+    fn rebuilt(&mut self) {
+        self.retry_at = None;
+        self.rebuilt_at = Some(Instant::now());
+    }
+}
+
+/// Re-reads the config on SIGHUP or `reloadConfig` and applies what can
+/// change in a running daemon. Everything that can't is reported as
+/// rejected and keeps its old value until a restart.
+struct Reloader {
+    log: LogControl,
+    chains: Chains,
+    required_peers: RequiredPeers,
+    tor_proxy: TorProxy,
+    config: Config,
+}
+
+impl Reloader {
+    /// Returns the report and the networks whose light client must be rebuilt.
+    /// `checkpoints` has one entry per network, in the order of `chains`.
+    fn reload(&mut self, checkpoints: &mut [Checkpoints]) -> (ReloadReport, Vec<Network>) {
+        let mut report = ReloadReport::default();
+        // Settings shared by every network, and ones for the primary only.
+        let mut rebuild_all = false;
+        let mut rebuild_primary = false;
+        let conf = kyotod::paths::expand(&datadir_hint()).join("kyotod.conf");
+        let mut new = match Config::including_optional_config_files([&conf]) {
+            Ok((c, _)) => c,
+            Err(e) => {
+                report.rejected.push(format!("config: {e}"));
+                return self.log(report, Vec::new());
+            }
+        };
+        let old = &self.config;
+        let chains = self.chains.clone();
+        let primary = &chains[0];
+
+        let fixed = [
+            ("network", old.network != new.network),
+            ("extra_networks", old.extra_networks != new.extra_networks),
+            ("datadir", old.datadir != new.datadir),
+            ("socket", old.socket != new.socket),
+            ("daemon", old.daemon != new.daemon),
+            ("socket_group", old.socket_group != new.socket_group),
+            ("allow_uids", old.allow_uids != new.allow_uids),
+            ("allow_gids", old.allow_gids != new.allow_gids),
+            ("remote_listen", old.remote_listen != new.remote_listen),
+            ("watch_wallets", old.watch_wallets != new.watch_wallets),
+            ("peer_tip_check", old.peer_tip_check != new.peer_tip_check),
+            ("log_file", old.log_file != new.log_file),
+            ("log_format", old.log_format != new.log_format),
+            (
+                "log_max_size_mb",
+                old.log_max_size_mb != new.log_max_size_mb,
+            ),
+            ("log_rotate", old.log_rotate != new.log_rotate),
+            ("log_keep", old.log_keep != new.log_keep),
+            ("log_buffer", old.log_buffer != new.log_buffer),
+            ("metrics_listen", old.metrics_listen != new.metrics_listen),
+            (
+                "metrics_wallet_balances",
+                old.metrics_wallet_balances != new.metrics_wallet_balances,
+            ),
+        ];
+        for (name, changed) in fixed {
+            if changed {
+                report
+                    .rejected
+                    .push(format!("{name}: changes need a restart"));
+            }
+        }
+        // Whatever is rejected keeps the value in force in `new`, so the next
+        // reload compares against that and reports the change again.
+        new.network = old.network.clone();
+        new.extra_networks = old.extra_networks.clone();
+        new.datadir = old.datadir.clone();
+        new.socket = old.socket.clone();
+        new.daemon = old.daemon;
+        new.socket_group = old.socket_group.clone();
+        new.allow_uids = old.allow_uids.clone();
+        new.allow_gids = old.allow_gids.clone();
+        new.remote_listen = old.remote_listen.clone();
+        new.watch_wallets = old.watch_wallets;
+        new.peer_tip_check = old.peer_tip_check;
+        new.log_file = old.log_file.clone();
+        new.log_format = old.log_format.clone();
+        new.log_max_size_mb = old.log_max_size_mb;
+        new.log_rotate = old.log_rotate.clone();
+        new.log_keep = old.log_keep;
+        new.log_buffer = old.log_buffer;
+        new.metrics_listen = old.metrics_listen.clone();
+        new.metrics_wallet_balances = old.metrics_wallet_balances;
+
+        if old.log_level != new.log_level {
+            match self.log.set(&new.log_level) {
+                Ok(()) => report.applied.push(format!("log_level: {}", new.log_level)),
+                Err(e) => {
+                    report.rejected.push(e);
+                    new.log_level = old.log_level.clone();
+                }
+            }
+        }
+        if old.required_peers != new.required_peers {
+            let n = new.required_peers.clamp(1, 15);
+            *self.required_peers.lock().unwrap() = n;
+            report.applied.push(format!("required_peers: {n}"));
+            rebuild_all = true;
+        }
+        if old.trusted_peers != new.trusted_peers {
+            let peers = configured_peers(&new, primary.network);
+            report
+                .applied
+                .push(format!("trusted_peers: {} peer(s)", peers.len()));
+            *primary.trusted_peers.lock().unwrap() = peers;
+            rebuild_primary = true;
+        }
+        if old.proxy != new.proxy {
+            let proxy = configured_proxy(&new);
+            match new.proxy.as_deref() {
+                Some(p) if proxy.is_none() => report
+                    .rejected
+                    .push(format!("proxy: invalid address '{p}'")),
+                Some(p) => report.applied.push(format!("proxy: {p}")),
+                None => report.applied.push("proxy: disabled".into()),
+            }
+            if new.proxy.is_none() || proxy.is_some() {
+                *self.tor_proxy.lock().unwrap() = proxy;
+                rebuild_all = true;
+            } else {
+                new.proxy = old.proxy.clone();
+            }
+        }
+        if old.checkpoints != new.checkpoints {
+            let loaded = match new.checkpoints.as_deref() {
+                Some(path) => CheckpointFile::load(&kyotod::paths::expand(path)).map(Some),
+                None => Ok(None),
+            };
+            match loaded {
+                Ok(file) => {
+                    for (i, (cps, chain)) in checkpoints.iter_mut().zip(chains.iter()).enumerate() {
+                        let mut fresh = Checkpoints::builtin(chain.network);
+                        let n = file
+                            .as_ref()
+                            .map_or(0, |f| fresh.merge(f, chain.network, i == 0));
+                        *cps = fresh;
+                        report.applied.push(format!(
+                            "checkpoints: {}: {n} loaded, {} total; used from the next rebuild",
+                            chain.network,
+                            cps.len()
+                        ));
+                    }
+                }
+                Err(e) => {
+                    report.rejected.push(format!("checkpoints: {e}"));
+                    new.checkpoints = old.checkpoints.clone();
+                }
+            }
+        }
+        if old.fee_blocks != new.fee_blocks {
+            for chain in chains.iter() {
+                chain.fees.lock().unwrap().set_depth(new.fee_blocks);
+            }
+            report.applied.push(format!(
+                "fee_blocks: {}; used from the next block",
+                new.fee_blocks
+            ));
+        }
+        if old.rebroadcast != new.rebroadcast
+            || old.rebroadcast_base_secs != new.rebroadcast_base_secs
+            || old.rebroadcast_max_secs != new.rebroadcast_max_secs
+            || old.rebroadcast_confirmations != new.rebroadcast_confirmations
+        {
+            match rebroadcast_policy(&new) {
+                Ok(policy) => {
+                    for chain in chains.iter() {
+                        chain.pending.lock().unwrap().set_policy(policy);
+                    }
+                    report.applied.push(format!(
+                        "rebroadcast: {} (base {}s, max {}s, until {} deep)",
+                        if policy.enabled { "on" } else { "off" },
+                        policy.base_delay_secs,
+                        policy.max_delay_secs,
+                        policy.confirmations
+                    ));
+                }
+                Err(e) => {
+                    report.rejected.push(e);
+                    new.rebroadcast = old.rebroadcast;
+                    new.rebroadcast_base_secs = old.rebroadcast_base_secs;
+                    new.rebroadcast_max_secs = old.rebroadcast_max_secs;
+                    new.rebroadcast_confirmations = old.rebroadcast_confirmations;
+                }
+            }
+        }
+        let hooks = hook_settings(&new);
+        if hook_settings(old) != hooks {
+            report.applied.push(format!(
+                "walletnotify/blocknotify: {}, {}; at most {}/min, {}s timeout",
+                if hooks.walletnotify.is_some() {
+                    "set"
+                } else {
+                    "unset"
+                },
+                if hooks.blocknotify.is_some() {
+                    "set"
+                } else {
+                    "unset"
+                },
+                hooks.per_minute,
+                hooks.timeout.as_secs()
+            ));
+            // Every network's hooks share these settings.
+            primary.hooks.set(hooks);
+        }
+        if old.webhook_url != new.webhook_url
+            || old.webhook_secret != new.webhook_secret
+            || old.webhook_confirmations != new.webhook_confirmations
+            || old.webhook_max_delay_secs != new.webhook_max_delay_secs
+        {
+            match webhook_settings(&new) {
+                Ok(settings) => {
+                    report.applied.push(match &settings.endpoint {
+                        Some(e) => format!(
+                            "webhooks: {e}, payment_confirmed at {} confirmation(s)",
+                            settings.confirmations
+                        ),
+                        None => "webhooks: disabled".into(),
+                    });
+                    for chain in chains.iter() {
+                        chain
+                            .webhooks
+                            .lock()
+                            .unwrap()
+                            .set_settings(settings.clone());
+                    }
+                }
+                Err(e) => {
+                    report.rejected.push(e);
+                    new.webhook_url = old.webhook_url.clone();
+                    new.webhook_secret = old.webhook_secret.clone();
+                    new.webhook_confirmations = old.webhook_confirmations;
+                    new.webhook_max_delay_secs = old.webhook_max_delay_secs;
+                }
+            }
+        }
+        if old.broadcast_policy != new.broadcast_policy
+            || old.broadcast_peer != new.broadcast_peer
+            || old.broadcast_max_delay_secs != new.broadcast_max_delay_secs
+        {
+            match broadcast_policy(&new, true) {
+                Ok(policy) => {
+                    report.applied.push(format!(
+                        "broadcast: via {}, up to {}s delay",
+                        policy.route.describe(),
+                        policy.max_delay.as_secs()
+                    ));
+                    for (i, chain) in chains.iter().enumerate() {
+                        chain
+                            .broadcaster
+                            .set_policy(broadcast_policy(&new, i == 0).expect("checked above"));
+                    }
+                }
+                Err(e) => {
+                    report.rejected.push(e);
+                    new.broadcast_policy = old.broadcast_policy.clone();
+                    new.broadcast_peer = old.broadcast_peer.clone();
+                    new.broadcast_max_delay_secs = old.broadcast_max_delay_secs;
+                }
+            }
+        }
+
+        let mut rebuild = Vec::new();
+        for (i, chain) in chains.iter().enumerate() {
+            let label = if i == 0 {
+                String::new()
+            } else {
+                format!("{} ", chain.network)
+            };
+            let mut loaded = false;
+            let mut state = chain.state.lock().unwrap();
+            match state.load_new(&chain.layout, chain.network) {
+                Ok(outcomes) => {
+                    for (name, outcome) in outcomes {
+                        match outcome {
+                            Ok(()) => {
+                                report
+                                    .applied
+                                    .push(format!("{label}wallet '{name}' loaded"));
+                                loaded = true;
+                            }
+                            Err(e) => report.rejected.push(format!("{label}wallet '{name}': {e}")),
+                        }
+                    }
+                }
+                Err(e) => report.rejected.push(format!("{label}wallets: {e}")),
+            }
+            if (rebuild_all || (rebuild_primary && i == 0) || loaded) && !state.wallets.is_empty() {
+                rebuild.push(chain.network);
+            }
+        }
+
+        self.config = new;
+        self.log(report, rebuild)
+    }
+
+    fn log(&self, report: ReloadReport, rebuild: Vec<Network>) -> (ReloadReport, Vec<Network>) {
+        if report.applied.is_empty() && report.rejected.is_empty() {
+            info!(target: "node", "reload: nothing changed");
+        }
+        for m in &report.applied {
+            info!(target: "node", "reload: applied {m}");
+        }
+        for m in &report.rejected {
+            warn!(target: "node", "reload: rejected {m}");
+        }
+        if !rebuild.is_empty() {
+            let names: Vec<String> = rebuild.iter().map(ToString::to_string).collect();
+            info!(target: "node", "reload: rebuilding light client ({})", names.join(", "));
+        }
+        (report, rebuild)
+    }
+}
+
+fn hook_settings(config: &Config) -> hooks::Settings {
+    hooks::Settings {
+        walletnotify: config.walletnotify.clone(),
+        blocknotify: config.blocknotify.clone(),
+        per_minute: config.notify_max_per_minute,
+        timeout: Duration::from_secs(config.notify_timeout_secs.max(1)),
+    }
+}
+
+fn webhook_settings(config: &Config) -> Result<webhooks::Settings, String> {
+    let endpoint = match config.webhook_url.as_deref() {
+        Some(url) => Some(url.parse().map_err(|e| format!("webhook_url: {e}"))?),
+        None => None,
+    };
+    let secret = config.webhook_secret.clone().unwrap_or_default();
+    if endpoint.is_some() && secret.is_empty() {
+        return Err("webhook_url is set but webhook_secret is not".into());
+    }
+    Ok(webhooks::Settings {
+        endpoint,
+        secret,
+        confirmations: config.webhook_confirmations.max(1),
+        max_delay_secs: config.webhook_max_delay_secs,
+    })
+}
+
+/// `broadcast_peer` is a peer on the primary network; the others send over
+/// a separate connection to a seed's peer instead.
+fn broadcast_policy(config: &Config, primary: bool) -> Result<broadcast::Policy, String> {
+    let mut route = Route::parse(&config.broadcast_policy, config.broadcast_peer.as_deref())?;
+    if !primary && matches!(route, Route::Peer(_)) {
+        route = Route::Isolated;
+    }
+    Ok(broadcast::Policy {
+        route,
+        max_delay: Duration::from_secs(config.broadcast_max_delay_secs),
+    })
+}
+
+fn rebroadcast_policy(config: &Config) -> Result<rebroadcast::Policy, String> {
+    let base_delay_secs = config.rebroadcast_base_secs.max(1);
+    if config.rebroadcast_max_secs < base_delay_secs {
+        return Err(format!(
+            "rebroadcast_max_secs ({}) is below rebroadcast_base_secs ({base_delay_secs})",
+            config.rebroadcast_max_secs
+        ));
+    }
+    Ok(rebroadcast::Policy {
+        enabled: config.rebroadcast,
+        base_delay_secs,
+        max_delay_secs: config.rebroadcast_max_secs,
+        confirmations: config.rebroadcast_confirmations,
+    })
+}
+
+// The daemon always logs to a file (its stdout is detached); in the
+// foreground a file is optional and stdout is always on.
+fn log_path(config: &Config) -> std::path::PathBuf {
+    match config.log_file.as_deref() {
+        Some(p) => kyotod::paths::expand(p),
+        None => kyotod::paths::expand(&config.datadir).join("node.log"),
+    }
+}
+
+fn init_logging(config: &Config) -> Result<(LogControl, LogBuffer), String> {
+    let format = config.log_format.parse()?;
+    let rotation = Rotation {
+        max_bytes: config.log_max_size_mb.saturating_mul(1024 * 1024),
+        period: config.log_rotate.parse()?,
+        keep: config.log_keep,
+    };
+    let file = (config.daemon || config.log_file.is_some()).then(|| LogFile {
+        path: log_path(config),
+        rotation,
+        redirect_stdio: config.daemon,
+    });
+    let logs = LogRing::new(config.log_buffer as usize);
+    let mut extra: Vec<logging::BoxedLayer> = Vec::new();
+    if logs.capacity() > 0 {
+        extra.push(Box::new(logs.layer()));
+    }
+    let log = logging::init(
+        logging::Options {
+            directives: config.log_level.clone(),
+            format,
+            stdout: !config.daemon,
+            file,
+        },
+        extra,
+    )
+    .map_err(|e| format!("{}: {e}", log_path(config).display()))?;
+    Ok((log, logs))
+}
+
+// One line for `systemctl status`, with a part per network when there are
+// several.
+fn service_status(chains: &[Chain], handles: &[Option<SyncHandle>]) -> String {
+    let parts: Vec<String> = chains
+        .iter()
+        .zip(handles)
+        .map(|(chain, handle)| {
+            let status = chain_status(chain, handle.is_some());
+            if chains.len() > 1 {
+                format!("{}: {status}", chain.network)
+            } else {
+                status
+            }
+        })
+        .collect();
+    parts.join(" | ")
+}
+
+fn chain_status(chain: &Chain, syncing: bool) -> String {
+    let wallets = chain.state.lock().unwrap().wallets.len();
+    if !syncing {
+        return format!("{wallets} wallet(s); waiting for import");
+    }
+    if let Some(reason) = &chain.health.lock().unwrap().degraded {
+        return format!("{wallets} wallet(s); degraded: {reason}");
+    }
+    match *chain.progress.lock().unwrap() {
+        Some(p) => format!("{wallets} wallet(s); synced {p:.1}%"),
+        None => format!("{wallets} wallet(s); connecting to peers"),
+    }
+}
+
+// Pre-scan argv and the environment for the datadir so the config file inside
+// it can be located before configure_me runs. A `datadir` set in the config
+// file itself cannot move the config file.
+fn datadir_hint() -> String {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--datadir" {
+            if let Some(v) = args.next() {
+                return v;
+            }
+        } else if let Some(v) = arg.strip_prefix("--datadir=") {
+            return v.to_string();
+        }
+    }
+    std::env::var("KYOTOD_DATADIR").unwrap_or_else(|_| "~/.kyotod".into())
+}
+
+/// The config's `network`, then each of `extra_networks` not already listed.
+fn configured_networks(config: &Config) -> Result<Vec<Network>, String> {
+    let primary = Network::from_str(&config.network).map_err(|e| format!("network: {e}"))?;
+    let mut out = vec![primary];
+    let extra = config.extra_networks.as_deref().unwrap_or("");
+    for name in extra.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let network =
+            Network::from_str(name).map_err(|e| format!("extra_networks: '{name}': {e}"))?;
+        if !out.contains(&network) {
+            out.push(network);
+        }
+    }
+    Ok(out)
+}
+
+fn configured_peers(config: &Config, network: Network) -> Vec<std::net::SocketAddr> {
+    let Some(list) = config.trusted_peers.as_deref() else {
+        return Vec::new();
+    };
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match sync::resolve_peer(s, network) {
+            Ok(addr) => Some(addr),
+            Err(e) => {
+                warn!(target: "node", "trusted peer '{s}': {e}; skipping");
+                None
+            }
+        })
+        .collect()
+}
+
+fn configured_proxy(config: &Config) -> Option<std::net::SocketAddr> {
+    let proxy = config.proxy.as_deref()?;
+    match proxy.parse() {
+        Ok(addr) => Some(addr),
+        Err(e) => {
+            warn!(target: "node", "proxy '{proxy}': {e}; connecting directly");
+            None
+        }
+    }
+}
+
 // Look up a HashCheckpoint for each wallet that declared a BIP-139 birthday
 // (account.block_height) and hasn't yet synced past it. Hash resolution piggy-
-// backs on the *current* (about-to-be-shut-down) light client; if there's no
-// such client (first wallet ever) we just return an empty map and the wallet
-// falls back to ScanType::Sync from genesis.
+// backs on the *current* (about-to-be-shut-down) light client when there is
+// one. Otherwise (first wallet ever, or the header isn't known yet) the
+// wallet starts at the nearest compiled-in or user-supplied checkpoint at or
+// below its birthday, and only falls back to ScanType::Sync from genesis
+// when no such checkpoint exists.
 async fn resolve_scans(
     state: &Arc<Mutex<State>>,
     requester: Option<&Requester>,
+    checkpoints: &Checkpoints,
 ) -> HashMap<String, ScanType> {
     let mut out = HashMap::new();
     let candidates: Vec<(String, u32)> = {
@@ -175,26 +1294,40 @@ async fn resolve_scans(
             })
             .collect()
     };
-    let Some(req) = requester else {
-        for (name, h) in candidates {
-            warn!(target: "node", "wallet '{name}': birthday {h} but no running client to resolve hash; syncing from genesis");
-        }
-        return out;
-    };
     for (name, h) in candidates {
-        match req.get_header(h).await {
-            Ok(Some(ih)) => {
+        if let Some(req) = requester {
+            match req.get_header(h).await {
+                Ok(Some(ih)) => {
+                    out.insert(
+                        name.clone(),
+                        ScanType::Recovery {
+                            used_script_index: 0,
+                            checkpoint: HashCheckpoint::new(h, ih.header.block_hash()),
+                        },
+                    );
+                    info!(target: "node", "wallet '{name}': starting recovery at height {h}");
+                    continue;
+                }
+                Ok(None) => {
+                    warn!(target: "node", "wallet '{name}': header at {h} not yet in chain")
+                }
+                Err(e) => warn!(target: "node", "wallet '{name}': get_header({h}): {e}"),
+            }
+        }
+        match checkpoints.at_or_below(h) {
+            Some(checkpoint) => {
+                info!(target: "node", "wallet '{name}': birthday {h}; starting recovery at nearest checkpoint {}", checkpoint.height);
                 out.insert(
-                    name.clone(),
+                    name,
                     ScanType::Recovery {
                         used_script_index: 0,
-                        checkpoint: HashCheckpoint::new(h, ih.header.block_hash()),
+                        checkpoint,
                     },
                 );
-                info!(target: "node", "wallet '{name}': starting recovery at height {h}");
             }
-            Ok(None) => warn!(target: "node", "wallet '{name}': header at {h} not yet in chain; syncing from genesis"),
-            Err(e) => warn!(target: "node", "wallet '{name}': get_header({h}): {e}; syncing from genesis"),
+            None => {
+                warn!(target: "node", "wallet '{name}': birthday {h} but no checkpoint at or below it; syncing from genesis")
+            }
         }
     }
     out
@@ -1,9 +1,12 @@
+use std::collections::VecDeque;
 use std::error::Error;
 use std::io::{self, Stdout};
+use std::str::FromStr;
 use std::time::Duration;
 
 use bdk_kyoto::bip157::tokio;
 use bdk_wallet::bitcoin::hex::DisplayHex;
+use bdk_wallet::bitcoin::Network;
 use clap::Parser;
 use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::execute;
@@ -11,8 +14,10 @@ use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use futures::StreamExt;
-use kyotod::paths;
-use kyotod::server_capnp::server;
+use kyotod::logbuf::{LogEntry, LogFilter};
+use kyotod::remote::{self, ConnectString};
+use kyotod::server_capnp::{log_sink, log_tail, reorg, server};
+use kyotod::{bip21, clock, control, paths};
 use qrcode::{Color as QrColor, QrCode};
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
@@ -20,10 +25,16 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap};
 use ratatui::{Frame, Terminal};
-use tokio::net::UnixStream;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
 const DEFAULT_DATADIR: &str = "~/.kyotod";
+/// Lines the log screen keeps; older ones scroll off the top.
+const LOG_LINES: usize = 5000;
+/// Buffered lines asked for when the log screen opens.
+const LOG_BACKLOG: u32 = 500;
+const LOG_TARGETS: [Option<&str>; 4] = [None, Some("node"), Some("wallet"), Some("ipc")];
 
 type Backend = CrosstermBackend<Stdout>;
 type Term = Terminal<Backend>;
@@ -33,6 +44,20 @@ type Term = Terminal<Backend>;
 struct Cli {
     #[arg(long, default_value = DEFAULT_DATADIR)]
     datadir: String,
+    /// IPC socket path; defaults to <datadir>/node.sock.
+    #[arg(long)]
+    socket: Option<String>,
+    /// Connection string printed by `kyotod pair`; talks to the daemon over
+    /// TCP instead of the local socket.
+    #[arg(long, conflicts_with = "socket")]
+    connect: Option<String>,
+    /// Key this client presents to a remote daemon; created on first use.
+    /// Defaults to <datadir>/client.key.
+    #[arg(long)]
+    client_key: Option<String>,
+    /// Print the client's public key, for `kyotod pair`, and exit.
+    #[arg(long)]
+    show_client_key: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -45,6 +70,9 @@ enum Screen {
     Import,
     Network,
     Broadcast,
+    Receive,
+    Logs,
+    Reorgs,
 }
 
 #[derive(Default, Clone, Copy, PartialEq, Eq)]
@@ -77,7 +105,12 @@ struct App {
     height: Option<u32>,
     peer_count: Option<usize>,
     progress: Option<f32>,
+    // Why the daemon's light client is down, while it restarts it.
+    degraded: Option<String>,
+    sync: Option<SyncView>,
     network_name: Option<String>,
+    // Every network the daemon follows; `N` cycles through them.
+    networks: Vec<String>,
     list: ListState,
     last_error: Option<String>,
     last_info: Option<String>,
@@ -88,17 +121,148 @@ struct App {
     // Wallet-detail state (keyed to the currently focused wallet name).
     focus_wallet: Option<String>,
     receive_address: Option<String>,
+    receive_uri: Option<String>,
     history: Option<String>,
 
     // Forms.
     form: SendForm,
+    receive: ReceiveForm,
     create: CreateForm,
     import: ImportForm,
     network: NetworkForm,
     broadcast: BroadcastForm,
     required_peers: Option<u8>,
+    // Fee presets fetched when the send form opens.
+    fees: Option<FeePresets>,
     // Result of the most recent buildTransaction.
     result: Option<BuildResult>,
+    logs: LogView,
+    reorgs: Vec<ReorgRow>,
+    // Reorgs already shown on the reorg screen; newer ones get a notice.
+    reorgs_seen: usize,
+}
+
+/// The log screen. The daemon sends everything it has buffered; level and
+/// target filters apply locally so changing them is instant.
+struct LogView {
+    lines: VecDeque<LogEntry>,
+    level: tracing::Level,
+    target: usize, // index into LOG_TARGETS
+    follow: bool,
+    // Filtered lines hidden below the view; always 0 while following.
+    scroll: usize,
+    missed: u64,
+    // Batches from an earlier tail can still arrive after reopening.
+    generation: u64,
+    tail: Option<log_tail::Client>,
+    sink: Option<mpsc::UnboundedSender<LogBatch>>,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            level: tracing::Level::INFO,
+            target: 0,
+            follow: true,
+            scroll: 0,
+            missed: 0,
+            generation: 0,
+            tail: None,
+            sink: None,
+        }
+    }
+}
+
+impl LogView {
+    fn filter(&self) -> LogFilter {
+        LogFilter {
+            min: self.level,
+            targets: LOG_TARGETS[self.target]
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+        }
+    }
+    fn visible(&self) -> Vec<&LogEntry> {
+        let filter = self.filter();
+        self.lines.iter().filter(|e| filter.matches(e)).collect()
+    }
+    fn apply(&mut self, batch: LogBatch) {
+        if batch.generation != self.generation {
+            return;
+        }
+        self.missed += batch.missed;
+        let filter = self.filter();
+        if !self.follow {
+            // Keep the view still while lines arrive below it.
+            self.scroll += batch.entries.iter().filter(|e| filter.matches(e)).count();
+        }
+        self.lines.extend(batch.entries);
+        while self.lines.len() > LOG_LINES {
+            self.lines.pop_front();
+        }
+    }
+    fn scroll_up(&mut self, n: usize) {
+        let max = self.visible().len().saturating_sub(1);
+        self.scroll = (self.scroll + n).min(max);
+        self.follow = false;
+    }
+    fn scroll_down(&mut self, n: usize) {
+        self.scroll = self.scroll.saturating_sub(n);
+        if self.scroll == 0 {
+            self.follow = true;
+        }
+    }
+    // Filters change what is below the view, so start again from the end.
+    fn refilter(&mut self) {
+        self.scroll = 0;
+        self.follow = true;
+    }
+}
+
+struct LogBatch {
+    generation: u64,
+    entries: Vec<LogEntry>,
+    missed: u64,
+}
+
+/// Receives `tailLogs` pushes and hands them to the UI loop.
+struct LogSink {
+    generation: u64,
+    tx: mpsc::UnboundedSender<LogBatch>,
+}
+
+impl log_sink::Server for LogSink {
+    async fn push(
+        self: capnp::capability::Rc<Self>,
+        params: log_sink::PushParams,
+        _: log_sink::PushResults,
+    ) -> Result<(), capnp::Error> {
+        let p = params.get()?;
+        let mut entries = Vec::new();
+        for e in p.get_entries()? {
+            entries.push(LogEntry {
+                seq: e.get_seq(),
+                time_ms: e.get_time_ms(),
+                level: e
+                    .get_level()?
+                    .to_str()?
+                    .parse()
+                    .unwrap_or(tracing::Level::INFO),
+                target: e.get_target()?.to_string()?,
+                message: e.get_message()?.to_string()?,
+            });
+        }
+        let batch = LogBatch {
+            generation: self.generation,
+            entries,
+            missed: p.get_missed(),
+        };
+        self.tx
+            .send(batch)
+            .map_err(|_| capnp::Error::failed("log screen closed".into()))
+    }
 }
 
 struct NetworkForm {
@@ -132,6 +296,14 @@ struct CreateForm {
     focus: u8, // 0=name, 1=external, 2=change, 3=birthday
 }
 
+#[derive(Default)]
+struct ReceiveForm {
+    amount: String,
+    label: String,
+    message: String,
+    focus: u8, // 0=amount, 1=label, 2=message
+}
+
 #[derive(Default)]
 struct ImportForm {
     path: String,
@@ -148,6 +320,46 @@ struct WalletRow {
     name: String,
     sats: u64,
     active: bool,
+    confirmed: u64,
+    trusted_pending: u64,
+    untrusted_pending: u64,
+    immature: u64,
+    spendable: u64,
+    status: Option<WalletStatus>,
+}
+
+/// The light client's sync stages, from `syncState`.
+struct SyncView {
+    header_height: Option<u32>,
+    /// What one peer outside the light client's set announced.
+    peer_tip: Option<u32>,
+    // (filter headers, filters, total to check)
+    stages: Option<(u32, u32, u32)>,
+    blocks: u64,
+    eta_secs: Option<u64>,
+    warnings: Vec<(u64, String)>,
+}
+
+/// Where a wallet is in its sync, from `walletStatus`.
+struct WalletStatus {
+    height: u32,
+    hash: String,
+    recovery_from: Option<u32>,
+    external: Option<u32>,
+    internal: Option<u32>,
+    last_update: Option<u64>,
+    transactions: u32,
+}
+
+struct ReorgRow {
+    time: u64,
+    depth: u32,
+    common_height: u32,
+    old_tip: (u32, String),
+    new_tip: (u32, String),
+    wallets: Vec<String>,
+    // (wallet, txid, old height, new height if confirmed again)
+    moved: Vec<(String, String, u32, Option<u32>)>,
 }
 
 #[derive(Default)]
@@ -157,15 +369,26 @@ struct SendForm {
     sat_per_vb: String,
     out_path: String,
     drain: bool,
+    // Label from a pasted BIP-21 URI, shown for reference only.
+    label: Option<String>,
     focus: u8, // 0=recipient, 1=sats, 2=sat_per_vb, 3=out_path
 }
 
+#[derive(Clone, Copy)]
+struct FeePresets {
+    low: f64,
+    medium: f64,
+    high: f64,
+}
+
 struct BuildResult {
     psbt_path: String,
     txid: String,
     fee_sats: u64,
     signed: bool,
     raw_tx: Vec<u8>,
+    warning: Option<String>,
+    label: Option<String>,
     broadcast_txid: Option<String>,
 }
 
@@ -212,16 +435,51 @@ impl App {
         if snap.progress.is_some() {
             self.progress = snap.progress;
         }
+        if let Some(d) = snap.degraded {
+            self.degraded = Some(d).filter(|d| !d.is_empty());
+        }
+        if snap.sync.is_some() {
+            self.sync = snap.sync;
+        }
         if snap.required_peers.is_some() {
             self.required_peers = snap.required_peers;
         }
         if snap.network.is_some() {
             self.network_name = snap.network;
         }
+        if let Some(names) = snap.networks {
+            self.networks = names;
+        }
+        if let Some(rows) = snap.reorgs {
+            // The daemon keeps a bounded history and restarts empty.
+            self.reorgs_seen = self.reorgs_seen.min(rows.len());
+            self.reorgs = rows;
+        }
         if let Some(e) = snap.error {
             self.last_error = Some(e);
         }
     }
+
+    /// Forget everything that belonged to the previous network.
+    fn switched(&mut self, network: String) {
+        self.screen_stack.clear();
+        self.wallets.clear();
+        self.list.select(None);
+        self.height = None;
+        self.peer_count = None;
+        self.progress = None;
+        self.degraded = None;
+        self.sync = None;
+        self.reorgs.clear();
+        self.reorgs_seen = 0;
+        self.focus_wallet = None;
+        self.receive_address = None;
+        self.receive_uri = None;
+        self.history = None;
+        self.last_error = None;
+        self.last_info = Some(format!("switched to {network}"));
+        self.network_name = Some(network);
+    }
 }
 
 #[derive(Default)]
@@ -230,8 +488,13 @@ struct Snapshot {
     height: Option<u32>,
     peer_count: Option<usize>,
     progress: Option<f32>,
+    // Empty when healthy; `None` when syncProgress didn't answer.
+    degraded: Option<String>,
+    sync: Option<SyncView>,
     required_peers: Option<u8>,
     network: Option<String>,
+    networks: Option<Vec<String>>,
+    reorgs: Option<Vec<ReorgRow>>,
     error: Option<String>,
 }
 
@@ -244,6 +507,8 @@ enum Action {
     Back,
     SetActive,
     RevealAddress,
+    OpenReceive,
+    SubmitReceive,
     OpenSend,
     SubmitSend,
     Broadcast,
@@ -260,21 +525,47 @@ enum Action {
     DecreasePeers,
     ToggleTorEnabled,
     ApplyTor,
+    OpenLogs,
+    NextNetwork,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
+    let key_path = match cli.client_key.as_deref() {
+        Some(p) => paths::expand(p),
+        None => paths::expand(&cli.datadir).join("client.key"),
+    };
+    if cli.show_client_key {
+        println!("{}", load_client_key(&key_path)?.public);
+        return Ok(());
+    }
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
     rt.block_on(tokio::task::LocalSet::new().run_until(async move {
-        let client = match connect(&cli.datadir).await {
+        let client = match cli.connect.as_deref() {
+            Some(s) => connect_remote(s, &key_path).await,
+            None => {
+                let sock = match cli.socket.as_deref() {
+                    Some(s) => paths::expand(s),
+                    None => paths::expand(&cli.datadir).join("node.sock"),
+                };
+                connect(&sock).await
+            }
+        };
+        let client = match client {
             Ok(c) => c,
             Err(e) => {
                 eprintln!("error: {e}");
                 std::process::exit(1);
             }
         };
+        // Remote connections carry their paired tier already.
+        let client = if cli.connect.is_some() {
+            client
+        } else {
+            authenticate(client, &paths::expand(&cli.datadir)).await
+        };
         let mut terminal = setup_terminal()?;
         let res = run_app(&mut terminal, client).await;
         restore_terminal(&mut terminal)?;
@@ -282,24 +573,52 @@ fn main() -> Result<(), Box<dyn Error>> {
     }))
 }
 
-async fn connect(datadir: &str) -> Result<server::Client, Box<dyn Error>> {
-    let sock = paths::expand(datadir).join("node.sock");
-    let stream = UnixStream::connect(&sock)
+async fn connect(sock: &std::path::Path) -> Result<server::Client, Box<dyn Error>> {
+    Ok(control::connect(sock)
         .await
-        .map_err(|e| format!("connect {}: {e}", sock.display()))?;
-    let (r, w) = stream.into_split();
-    let r = futures::io::BufReader::new(r.compat());
-    let w = futures::io::BufWriter::new(w.compat_write());
-    let net = capnp_rpc::twoparty::VatNetwork::new(
-        r,
-        w,
-        capnp_rpc::rpc_twoparty_capnp::Side::Client,
-        Default::default(),
-    );
-    let mut rpc = capnp_rpc::RpcSystem::new(Box::new(net), None);
-    let client: server::Client = rpc.bootstrap(capnp_rpc::rpc_twoparty_capnp::Side::Server);
-    tokio::task::spawn_local(rpc);
-    Ok(client)
+        .map_err(|e| format!("connect {}: {e}", sock.display()))?)
+}
+
+async fn connect_remote(s: &str, key: &std::path::Path) -> Result<server::Client, Box<dyn Error>> {
+    let target = ConnectString::from_str(s)?;
+    let key = load_client_key(key)?;
+    let stream = TcpStream::connect(&target.addr)
+        .await
+        .map_err(|e| format!("connect {}: {e}", target.addr))?;
+    let plain = remote::connect(stream, &target.server, &key)
+        .await
+        .map_err(|e| format!("handshake with {}: {e}", target.addr))?;
+    let (r, w) = tokio::io::split(plain);
+    Ok(control::bootstrap(r, w))
+}
+
+fn load_client_key(path: &std::path::Path) -> Result<remote::Keypair, Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(remote::Keypair::load_or_create(path)
+        .map_err(|e| format!("client key {}: {e}", path.display()))?)
+}
+
+// Upgrade the read-only bootstrap capability with the strongest cookie we can
+// read. Without one the TUI still works for browsing, and the daemon rejects
+// anything that needs more.
+async fn authenticate(client: server::Client, datadir: &std::path::Path) -> server::Client {
+    for name in ["admin.cookie", "spender.cookie"] {
+        let Ok(token) = std::fs::read_to_string(datadir.join(name)) else {
+            continue;
+        };
+        let mut req = client.authenticate_request();
+        req.get().set_token(token.trim());
+        match req.send().promise.await {
+            Ok(resp) => match resp.get().and_then(|r| r.get_server()) {
+                Ok(upgraded) => return upgraded,
+                Err(e) => eprintln!("warning: {name}: {e}"),
+            },
+            Err(e) => eprintln!("warning: {name}: {}", clean(&e.to_string())),
+        }
+    }
+    client
 }
 
 fn setup_terminal() -> Result<Term, Box<dyn Error>> {
@@ -316,25 +635,32 @@ fn restore_terminal(terminal: &mut Term) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn run_app(terminal: &mut Term, client: server::Client) -> Result<(), Box<dyn Error>> {
+async fn run_app(terminal: &mut Term, mut client: server::Client) -> Result<(), Box<dyn Error>> {
     let mut app = App::default();
     let mut events = EventStream::new();
+    let (log_tx, mut log_rx) = mpsc::unbounded_channel::<LogBatch>();
+    app.logs.sink = Some(log_tx);
 
     // Poll on a dedicated task so a slow RPC can never starve key input
     // (Ctrl+C, q, navigation). The main loop just races events against fresh
     // snapshots coming over the channel.
+    // Switching networks hands the poller a new client; a snapshot taken
+    // with the old one is dropped.
     let (snap_tx, mut snap_rx) = tokio::sync::mpsc::channel::<Snapshot>(2);
-    let poll_client = client.clone();
+    let (client_tx, mut client_rx) = tokio::sync::watch::channel(client.clone());
     tokio::task::spawn_local(async move {
-        // First snapshot fires immediately so the UI isn't blank for 2s.
-        if snap_tx.send(poll(&poll_client).await).await.is_err() {
-            return;
-        }
         let mut tick = tokio::time::interval(Duration::from_secs(2));
-        tick.tick().await; // consume the immediate tick
         loop {
-            tick.tick().await;
+            // The first tick fires immediately so the UI isn't blank for 2s.
+            tokio::select! {
+                _ = tick.tick() => {}
+                changed = client_rx.changed() => if changed.is_err() { break },
+            }
+            let poll_client = client_rx.borrow_and_update().clone();
             let snap = poll(&poll_client).await;
+            if client_rx.has_changed().unwrap_or(true) {
+                continue;
+            }
             if snap_tx.send(snap).await.is_err() {
                 break;
             }
@@ -356,20 +682,78 @@ async fn run_app(terminal: &mut Term, client: server::Client) -> Result<(), Box<
                 app.apply(snap);
                 Action::None
             }
+            Some(batch) = log_rx.recv() => {
+                app.logs.apply(batch);
+                Action::None
+            }
         };
+        if let Action::NextNetwork = action {
+            match next_network(&app, &client).await {
+                Ok(Some((name, next))) => {
+                    app.switched(name);
+                    client = next;
+                    let _ = client_tx.send(client.clone());
+                }
+                Ok(None) => app.last_info = Some("the daemon follows only one network".into()),
+                Err(e) => app.last_error = Some(e),
+            }
+            continue;
+        }
         dispatch(&mut app, action, &client).await;
     }
     Ok(())
 }
 
+/// A client bound to the network after the current one, if the daemon
+/// follows more than one.
+async fn next_network(
+    app: &App,
+    client: &server::Client,
+) -> Result<Option<(String, server::Client)>, String> {
+    let resp = client
+        .networks_request()
+        .send()
+        .promise
+        .await
+        .map_err(|e| format!("networks: {}", clean(&e.to_string())))?;
+    let names: Vec<String> = resp
+        .get()
+        .and_then(|r| r.get_names())
+        .map_err(|e| format!("networks: {e}"))?
+        .iter()
+        .filter_map(|n| n.ok()?.to_string().ok())
+        .collect();
+    if names.len() < 2 {
+        return Ok(None);
+    }
+    let current = names
+        .iter()
+        .position(|n| Some(n) == app.network_name.as_ref())
+        .unwrap_or(0);
+    let name = names[(current + 1) % names.len()].clone();
+    let mut req = client.use_network_request();
+    req.get().set_name(name.as_str());
+    let resp = req
+        .send()
+        .promise
+        .await
+        .map_err(|e| format!("useNetwork: {}", clean(&e.to_string())))?;
+    let next = resp
+        .get()
+        .and_then(|r| r.get_server())
+        .map_err(|e| format!("useNetwork: {e}"))?;
+    Ok(Some((name, next)))
+}
+
 async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
     match action {
-        Action::None => {}
+        Action::None | Action::NextNetwork => {}
         Action::Quit => app.quit = true,
         Action::OpenWallet => {
             if let Some(row) = app.focused_row() {
                 app.focus_wallet = Some(row.name.clone());
                 app.receive_address = None;
+                app.receive_uri = None;
                 app.history = None;
                 app.push(Screen::Wallet);
                 // Fetch history once on entry.
@@ -384,6 +768,7 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
             if app.screen() == Screen::Wallets {
                 app.focus_wallet = None;
                 app.receive_address = None;
+                app.receive_uri = None;
                 app.history = None;
                 app.last_error = None;
                 app.last_info = None;
@@ -391,6 +776,37 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
             if app.screen() != Screen::Send {
                 app.form = SendForm::default();
             }
+            if app.screen() != Screen::Logs {
+                // Releasing the capability ends the tail on the daemon.
+                app.logs.tail = None;
+            }
+        }
+        Action::OpenLogs => {
+            let Some(tx) = app.logs.sink.clone() else {
+                return;
+            };
+            app.logs.generation += 1;
+            app.logs.lines.clear();
+            app.logs.missed = 0;
+            app.logs.refilter();
+            let mut req = client.tail_logs_request();
+            req.get().set_from_level("trace");
+            req.get().set_backlog(LOG_BACKLOG);
+            req.get().set_sink(capnp_rpc::new_client(LogSink {
+                generation: app.logs.generation,
+                tx,
+            }));
+            match req.send().promise.await {
+                Ok(resp) => match resp.get().and_then(|r| r.get_tail()) {
+                    Ok(tail) => {
+                        app.logs.tail = Some(tail);
+                        app.last_error = None;
+                    }
+                    Err(e) => app.last_error = Some(format!("tail-logs: {e}")),
+                },
+                Err(e) => app.last_error = Some(format!("tail-logs: {}", clean(&e.to_string()))),
+            }
+            app.push(Screen::Logs);
         }
         Action::SetActive => {
             let Some(row) = app.focused_row() else { return };
@@ -403,16 +819,46 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
             }
         }
         Action::RevealAddress => {
-            if let Err(e) = fetch_receive(app, client).await {
+            if let Err(e) = fetch_receive(app, client, None).await {
                 app.last_error = Some(e);
             }
         }
+        Action::OpenReceive => {
+            app.receive = ReceiveForm::default();
+            app.last_error = None;
+            app.push(Screen::Receive);
+        }
+        Action::SubmitReceive => {
+            let amount = if app.receive.amount.trim().is_empty() {
+                0
+            } else {
+                match app.receive.amount.trim().parse::<u64>() {
+                    Ok(a) => a,
+                    Err(e) => {
+                        app.last_error = Some(format!("amount: {e}"));
+                        return;
+                    }
+                }
+            };
+            let label = app.receive.label.trim().to_string();
+            let message = app.receive.message.trim().to_string();
+            match fetch_receive(app, client, Some((amount, label, message))).await {
+                Ok(()) => {
+                    app.pop();
+                    app.last_error = None;
+                }
+                Err(e) => app.last_error = Some(e),
+            }
+        }
         Action::OpenSend => {
             app.form = SendForm::default();
+            app.fees = fetch_fee_presets(client).await;
             app.push(Screen::Send);
         }
         Action::SubmitSend => {
-            match submit_send(&app.form, client).await {
+            prefill_from_uri(app);
+            let default_rate = app.fees.map(|f| f.medium).unwrap_or(2.0);
+            match submit_send(&app.form, default_rate, client).await {
                 Ok(r) => {
                     app.result = Some(r);
                     app.push(Screen::Result);
@@ -526,18 +972,17 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
             req.get().set_path(app.broadcast.path.trim());
             req.get().set_finalize(app.broadcast.finalize);
             match req.send().promise.await {
-                Ok(resp) => match resp.get().and_then(|r| r.get_txid()) {
-                    Ok(t) => {
+                Ok(resp) => match resp.get().and_then(|r| Ok((r.get_txid()?, r.get_route()?))) {
+                    Ok((t, route)) => {
                         let txid = t.to_string().unwrap_or_default();
+                        let route = route.to_string().unwrap_or_default();
                         app.broadcast.last_txid = Some(txid.clone());
-                        app.last_info = Some(format!("broadcast {txid}"));
+                        app.last_info = Some(format!("broadcast {txid}: {route}"));
                         app.last_error = None;
                     }
                     Err(e) => app.last_error = Some(format!("broadcast: {e}")),
                 },
-                Err(e) => {
-                    app.last_error = Some(format!("broadcast: {}", clean(&e.to_string())))
-                }
+                Err(e) => app.last_error = Some(format!("broadcast: {}", clean(&e.to_string()))),
             }
         }
         Action::AddPeer => {
@@ -591,19 +1036,20 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
             let next = cur.saturating_sub(1).max(1);
             send_required_peers(app, client, next).await;
         }
-        Action::ShutdownDaemon => {
-            match client.shutdown_request().send().promise.await {
-                Ok(_) => app.quit = true,
-                Err(e) => {
-                    let msg = clean(&e.to_string());
-                    if msg.contains("disconnected") || msg.contains("EOF") || msg.contains("broken pipe") {
-                        app.quit = true;
-                    } else {
-                        app.last_error = Some(format!("shutdown: {msg}"));
-                    }
+        Action::ShutdownDaemon => match client.shutdown_request().send().promise.await {
+            Ok(_) => app.quit = true,
+            Err(e) => {
+                let msg = clean(&e.to_string());
+                if msg.contains("disconnected")
+                    || msg.contains("EOF")
+                    || msg.contains("broken pipe")
+                {
+                    app.quit = true;
+                } else {
+                    app.last_error = Some(format!("shutdown: {msg}"));
                 }
             }
-        }
+        },
         Action::Broadcast => {
             if let Some(res) = app.result.as_mut() {
                 if res.raw_tx.is_empty() {
@@ -613,14 +1059,17 @@ async fn dispatch(app: &mut App, action: Action, client: &server::Client) {
                 let mut req = client.broadcast_tx_request();
                 req.get().set_tx(&res.raw_tx);
                 match req.send().promise.await {
-                    Ok(resp) => match resp.get().and_then(|r| r.get_txid()) {
-                        Ok(t) => {
-                            let txid = t.to_string().unwrap_or_default();
-                            res.broadcast_txid = Some(txid);
-                            app.last_error = None;
+                    Ok(resp) => {
+                        match resp.get().and_then(|r| Ok((r.get_txid()?, r.get_route()?))) {
+                            Ok((t, route)) => {
+                                let txid = t.to_string().unwrap_or_default();
+                                app.last_info = Some(route.to_string().unwrap_or_default());
+                                res.broadcast_txid = Some(txid);
+                                app.last_error = None;
+                            }
+                            Err(e) => app.last_error = Some(format!("broadcast: {e}")),
                         }
-                        Err(e) => app.last_error = Some(format!("broadcast: {e}")),
-                    },
+                    }
                     Err(e) => {
                         app.last_error = Some(format!("broadcast: {}", clean(&e.to_string())))
                     }
@@ -651,9 +1100,7 @@ async fn send_required_peers(app: &mut App, client: &server::Client, n: u8) {
             }
             Err(e) => app.last_error = Some(format!("set-required-peers: {e}")),
         },
-        Err(e) => {
-            app.last_error = Some(format!("set-required-peers: {}", clean(&e.to_string())))
-        }
+        Err(e) => app.last_error = Some(format!("set-required-peers: {}", clean(&e.to_string()))),
     }
 }
 
@@ -674,23 +1121,66 @@ async fn fetch_history(app: &mut App, client: &server::Client) -> Result<(), Str
     Ok(())
 }
 
-async fn fetch_receive(app: &mut App, client: &server::Client) -> Result<(), String> {
-    let resp = client
-        .receive_request()
+async fn fetch_receive(
+    app: &mut App,
+    client: &server::Client,
+    request: Option<(u64, String, String)>,
+) -> Result<(), String> {
+    let mut req = client.receive_request();
+    if let Some((amount, label, message)) = &request {
+        let mut p = req.get();
+        p.set_amount(*amount);
+        p.set_label(label.as_str());
+        p.set_message(message.as_str());
+    }
+    let resp = req
         .send()
         .promise
         .await
         .map_err(|e| clean(&e.to_string()))?;
-    let addr = resp
-        .get()
-        .and_then(|r| r.get_address())
+    let r = resp.get().map_err(|e| e.to_string())?;
+    let addr = r
+        .get_address()
         .map_err(|e| e.to_string())?
         .to_string()
         .map_err(|e| e.to_string())?;
+    let uri = r
+        .get_uri()
+        .ok()
+        .and_then(|t| t.to_string().ok())
+        .filter(|u| !u.is_empty());
     app.receive_address = Some(addr);
+    app.receive_uri = uri;
     Ok(())
 }
 
+// If the recipient field holds a BIP-21 URI, copy its amount and label into
+// the form so the user sees what they are about to pay.
+fn prefill_from_uri(app: &mut App) {
+    let recipient = app.form.recipient.trim();
+    if !bip21::is_uri(recipient) {
+        app.form.label = None;
+        return;
+    }
+    let Some(network) = app
+        .network_name
+        .as_deref()
+        .and_then(|n| Network::from_str(n).ok())
+    else {
+        return;
+    };
+    match bip21::parse(recipient, network) {
+        Ok(uri) => {
+            if let Some(a) = uri.amount {
+                app.form.sats = a.to_sat().to_string();
+            }
+            app.form.label = uri.label;
+            app.last_error = None;
+        }
+        Err(e) => app.last_error = Some(format!("uri: {e}")),
+    }
+}
+
 async fn submit_create(form: &CreateForm, client: &server::Client) -> Result<String, String> {
     use bdk_wallet::miniscript::{Descriptor, DescriptorPublicKey};
     use bip139::{Account, WalletBackup, BIP_NUMBER, VERSION};
@@ -743,8 +1233,8 @@ async fn submit_import(form: &ImportForm, client: &server::Client) -> Result<Str
         return Err("path required".into());
     }
     let path = paths::expand(trimmed);
-    let json = std::fs::read_to_string(&path)
-        .map_err(|e| format!("read {}: {e}", path.display()))?;
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("read {}: {e}", path.display()))?;
     submit_import_json(client, json).await
 }
 
@@ -774,17 +1264,33 @@ async fn submit_import_json(client: &server::Client, json: String) -> Result<Str
     }
 }
 
-async fn submit_send(form: &SendForm, client: &server::Client) -> Result<BuildResult, String> {
+async fn fetch_fee_presets(client: &server::Client) -> Option<FeePresets> {
+    let mut req = client.estimate_fee_request();
+    req.get().set_target_blocks(3);
+    let resp = req.send().promise.await.ok()?;
+    let r = resp.get().ok()?;
+    if !r.get_has_data() {
+        return None;
+    }
+    Some(FeePresets {
+        low: r.get_low(),
+        medium: r.get_medium(),
+        high: r.get_high(),
+    })
+}
+
+async fn submit_send(
+    form: &SendForm,
+    default_rate: f64,
+    client: &server::Client,
+) -> Result<BuildResult, String> {
     let sats: u64 = if form.drain {
         0
     } else {
-        form.sats
-            .trim()
-            .parse()
-            .map_err(|e| format!("sats: {e}"))?
+        form.sats.trim().parse().map_err(|e| format!("sats: {e}"))?
     };
     let sat_per_vb: f64 = if form.sat_per_vb.trim().is_empty() {
-        2.0
+        default_rate
     } else {
         form.sat_per_vb
             .trim()
@@ -806,11 +1312,29 @@ async fn submit_send(form: &SendForm, client: &server::Client) -> Result<BuildRe
         .map_err(|e| clean(&e.to_string()))?;
     let r = resp.get().map_err(|e| e.to_string())?;
     Ok(BuildResult {
-        psbt_path: r.get_path().ok().and_then(|t| t.to_string().ok()).unwrap_or_default(),
-        txid: r.get_txid().ok().and_then(|t| t.to_string().ok()).unwrap_or_default(),
+        psbt_path: r
+            .get_path()
+            .ok()
+            .and_then(|t| t.to_string().ok())
+            .unwrap_or_default(),
+        txid: r
+            .get_txid()
+            .ok()
+            .and_then(|t| t.to_string().ok())
+            .unwrap_or_default(),
         fee_sats: r.get_fee_sats(),
         signed: r.get_signed(),
         raw_tx: r.get_raw_tx().map(|s| s.to_vec()).unwrap_or_default(),
+        warning: r
+            .get_warning()
+            .ok()
+            .and_then(|t| t.to_string().ok())
+            .filter(|w| !w.is_empty()),
+        label: r
+            .get_label()
+            .ok()
+            .and_then(|t| t.to_string().ok())
+            .filter(|l| !l.is_empty()),
         broadcast_txid: None,
     })
 }
@@ -820,16 +1344,25 @@ async fn poll(client: &server::Client) -> Snapshot {
     match client.balances_request().send().promise.await {
         Ok(resp) => match resp.get().and_then(|r| r.get_entries()) {
             Ok(entries) => {
-                let rows = entries
+                let mut rows = entries
                     .iter()
                     .filter_map(|e| {
                         Some(WalletRow {
                             name: e.get_name().ok()?.to_string().ok()?,
                             sats: e.get_sats(),
                             active: e.get_active(),
+                            confirmed: e.get_confirmed(),
+                            trusted_pending: e.get_trusted_pending(),
+                            untrusted_pending: e.get_untrusted_pending(),
+                            immature: e.get_immature(),
+                            spendable: e.get_spendable(),
+                            status: None,
                         })
                     })
-                    .collect();
+                    .collect::<Vec<_>>();
+                for row in &mut rows {
+                    row.status = fetch_wallet_status(client, &row.name).await;
+                }
                 snap.wallets = Some(rows);
             }
             Err(e) => snap.error = Some(format!("balances: {e}")),
@@ -841,10 +1374,11 @@ async fn poll(client: &server::Client) -> Snapshot {
     // against the previous one can otherwise stall indefinitely. Run them
     // concurrently so the slower one doesn't add to the faster one.
     let to = Duration::from_millis(500);
-    let (h, p, g) = tokio::join!(
+    let (h, p, g, st) = tokio::join!(
         tokio::time::timeout(to, client.height_request().send().promise),
         tokio::time::timeout(to, client.peers_request().send().promise),
         tokio::time::timeout(to, client.sync_progress_request().send().promise),
+        tokio::time::timeout(to, client.sync_state_request().send().promise),
     );
     if let Ok(Ok(resp)) = h {
         if let Ok(r) = resp.get() {
@@ -863,6 +1397,37 @@ async fn poll(client: &server::Client) -> Snapshot {
             if r.get_has_data() {
                 snap.progress = Some(r.get_percent());
             }
+            if let Ok(d) = r.get_degraded().and_then(|d| Ok(d.to_string()?)) {
+                snap.degraded = Some(d);
+            }
+        }
+    }
+    if let Ok(Ok(resp)) = st {
+        if let Ok(s) = resp.get().and_then(|r| r.get_state()) {
+            let warnings = s
+                .get_warnings()
+                .map(|ws| {
+                    ws.iter()
+                        .filter_map(|w| {
+                            Some((w.get_time(), w.get_message().ok()?.to_string().ok()?))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            snap.sync = Some(SyncView {
+                header_height: s.get_has_headers().then(|| s.get_header_height()),
+                peer_tip: s.get_has_peer_tip().then(|| s.get_peer_tip()),
+                stages: s.get_has_progress().then(|| {
+                    (
+                        s.get_filter_headers(),
+                        s.get_filters(),
+                        s.get_total_to_check(),
+                    )
+                }),
+                blocks: s.get_blocks_fetched(),
+                eta_secs: s.get_has_eta().then(|| s.get_eta_secs()),
+                warnings,
+            });
         }
     }
     if let Ok(resp) = client.get_required_peers_request().send().promise.await {
@@ -870,6 +1435,11 @@ async fn poll(client: &server::Client) -> Snapshot {
             snap.required_peers = Some(r.get_num());
         }
     }
+    if let Ok(resp) = client.reorgs_request().send().promise.await {
+        if let Ok(entries) = resp.get().and_then(|r| r.get_entries()) {
+            snap.reorgs = Some(entries.iter().filter_map(|e| reorg_row(e).ok()).collect());
+        }
+    }
     if let Ok(resp) = client.network_request().send().promise.await {
         if let Ok(name) = resp.get().and_then(|r| r.get_name()) {
             if let Ok(s) = name.to_string() {
@@ -877,18 +1447,73 @@ async fn poll(client: &server::Client) -> Snapshot {
             }
         }
     }
+    if let Ok(resp) = client.networks_request().send().promise.await {
+        if let Ok(names) = resp.get().and_then(|r| r.get_names()) {
+            snap.networks = Some(
+                names
+                    .iter()
+                    .filter_map(|n| n.ok()?.to_string().ok())
+                    .collect(),
+            );
+        }
+    }
     snap
 }
 
+async fn fetch_wallet_status(client: &server::Client, name: &str) -> Option<WalletStatus> {
+    let mut req = client.wallet_status_request();
+    req.get().set_name(name);
+    let resp = req.send().promise.await.ok()?;
+    let s = resp.get().ok()?.get_status().ok()?;
+    Some(WalletStatus {
+        height: s.get_height(),
+        hash: s.get_hash().ok()?.to_string().ok()?,
+        recovery_from: s.get_recovery().then(|| s.get_recovery_from()),
+        external: s.get_has_external().then(|| s.get_external_revealed()),
+        internal: s.get_has_internal().then(|| s.get_internal_revealed()),
+        last_update: Some(s.get_last_update()).filter(|t| *t > 0),
+        transactions: s.get_transactions(),
+    })
+}
+
+fn reorg_row(e: reorg::Reader<'_>) -> Result<ReorgRow, capnp::Error> {
+    let mut wallets = Vec::new();
+    for w in e.get_wallets()? {
+        wallets.push(w?.to_string()?);
+    }
+    let mut moved = Vec::new();
+    for m in e.get_moved()? {
+        moved.push((
+            m.get_wallet()?.to_string()?,
+            m.get_txid()?.to_string()?,
+            m.get_from_height(),
+            Some(m.get_to_height()).filter(|h| *h > 0),
+        ));
+    }
+    Ok(ReorgRow {
+        time: e.get_time(),
+        depth: e.get_depth(),
+        common_height: e.get_common_height(),
+        old_tip: (e.get_old_tip_height(), e.get_old_tip_hash()?.to_string()?),
+        new_tip: (e.get_new_tip_height(), e.get_new_tip_hash()?.to_string()?),
+        wallets,
+        moved,
+    })
+}
+
 fn clean(msg: &str) -> String {
     let s = msg.strip_prefix("Failed: ").unwrap_or(msg);
-    s.strip_prefix("remote exception: ").unwrap_or(s).to_string()
+    s.strip_prefix("remote exception: ")
+        .unwrap_or(s)
+        .to_string()
 }
 
 // --- input handling -------------------------------------------------------
 
 fn handle_event(app: &mut App, event: Event) -> Action {
-    let Event::Key(key) = event else { return Action::None };
+    let Event::Key(key) = event else {
+        return Action::None;
+    };
     if key.kind != KeyEventKind::Press {
         return Action::None;
     }
@@ -901,7 +1526,12 @@ fn handle_event(app: &mut App, event: Event) -> Action {
     }
     let on_form = matches!(
         app.screen(),
-        Screen::Send | Screen::Create | Screen::Import | Screen::Network | Screen::Broadcast
+        Screen::Send
+            | Screen::Create
+            | Screen::Import
+            | Screen::Network
+            | Screen::Broadcast
+            | Screen::Receive
     );
     if !on_form && key.code == KeyCode::Char('u') {
         app.unit = app.unit.toggle();
@@ -942,7 +1572,14 @@ fn handle_event(app: &mut App, event: Event) -> Action {
             KeyCode::Char('c') => Action::OpenCreate,
             KeyCode::Char('i') => Action::OpenImport,
             KeyCode::Char('n') => Action::OpenNetwork,
+            KeyCode::Char('N') => Action::NextNetwork,
             KeyCode::Char('b') => Action::OpenBroadcast,
+            KeyCode::Char('l') => Action::OpenLogs,
+            KeyCode::Char('o') => {
+                app.reorgs_seen = app.reorgs.len();
+                app.push(Screen::Reorgs);
+                Action::None
+            }
             KeyCode::Char('X') => {
                 app.confirm_shutdown = true;
                 Action::None
@@ -952,15 +1589,22 @@ fn handle_event(app: &mut App, event: Event) -> Action {
         Screen::Wallet => match key.code {
             KeyCode::Esc | KeyCode::Char('q') => Action::Back,
             KeyCode::Char('r') => Action::RevealAddress,
+            KeyCode::Char('R') => Action::OpenReceive,
             KeyCode::Char('s') => Action::OpenSend,
             KeyCode::Char('a') => Action::SetActive,
             _ => Action::None,
         },
         Screen::Send => handle_send(app, key),
+        Screen::Receive => handle_receive(app, key),
         Screen::Create => handle_create(app, key),
         Screen::Import => handle_import(app, key),
         Screen::Network => handle_network(app, key),
         Screen::Broadcast => handle_broadcast(app, key),
+        Screen::Logs => handle_logs(app, key),
+        Screen::Reorgs => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => Action::Back,
+            _ => Action::None,
+        },
         Screen::Result => match key.code {
             KeyCode::Esc | KeyCode::Char('q') => Action::Back,
             KeyCode::Char('b') => Action::Broadcast,
@@ -969,6 +1613,47 @@ fn handle_event(app: &mut App, event: Event) -> Action {
     }
 }
 
+fn handle_logs(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
+    let logs = &mut app.logs;
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => return Action::Back,
+        KeyCode::Up | KeyCode::Char('k') => logs.scroll_up(1),
+        KeyCode::Down | KeyCode::Char('j') => logs.scroll_down(1),
+        KeyCode::PageUp => logs.scroll_up(20),
+        KeyCode::PageDown => logs.scroll_down(20),
+        KeyCode::Home | KeyCode::Char('g') => logs.scroll_up(usize::MAX / 2),
+        KeyCode::End | KeyCode::Char('G') => logs.refilter(),
+        KeyCode::Char('f') => {
+            if logs.follow {
+                logs.follow = false;
+            } else {
+                logs.refilter();
+            }
+        }
+        KeyCode::Char(c @ '1'..='5') => {
+            logs.level = match c {
+                '1' => tracing::Level::ERROR,
+                '2' => tracing::Level::WARN,
+                '3' => tracing::Level::INFO,
+                '4' => tracing::Level::DEBUG,
+                _ => tracing::Level::TRACE,
+            };
+            logs.refilter();
+        }
+        KeyCode::Char('t') => {
+            logs.target = (logs.target + 1) % LOG_TARGETS.len();
+            logs.refilter();
+        }
+        KeyCode::Char('c') => {
+            logs.lines.clear();
+            logs.missed = 0;
+            logs.refilter();
+        }
+        _ => {}
+    }
+    Action::None
+}
+
 fn handle_create(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
     match key.code {
         KeyCode::Esc => Action::Back,
@@ -1085,10 +1770,16 @@ fn handle_send(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
         KeyCode::Esc => Action::Back,
         KeyCode::Enter => Action::SubmitSend,
         KeyCode::Tab => {
+            if app.form.focus == 0 {
+                prefill_from_uri(app);
+            }
             app.form.focus = (app.form.focus + 1) % 4;
             Action::None
         }
         KeyCode::BackTab => {
+            if app.form.focus == 0 {
+                prefill_from_uri(app);
+            }
             app.form.focus = (app.form.focus + 3) % 4;
             Action::None
         }
@@ -1096,6 +1787,17 @@ fn handle_send(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
             app.form.drain = !app.form.drain;
             Action::None
         }
+        KeyCode::Char(c @ '1'..='3') if key.modifiers.contains(KeyModifiers::ALT) => {
+            if let Some(f) = app.fees {
+                let rate = match c {
+                    '1' => f.low,
+                    '2' => f.medium,
+                    _ => f.high,
+                };
+                app.form.sat_per_vb = format!("{rate:.1}");
+            }
+            Action::None
+        }
         KeyCode::Backspace => {
             field_mut(&mut app.form).pop();
             Action::None
@@ -1108,6 +1810,38 @@ fn handle_send(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
     }
 }
 
+fn handle_receive(app: &mut App, key: crossterm::event::KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Esc => Action::Back,
+        KeyCode::Enter => Action::SubmitReceive,
+        KeyCode::Tab => {
+            app.receive.focus = (app.receive.focus + 1) % 3;
+            Action::None
+        }
+        KeyCode::BackTab => {
+            app.receive.focus = (app.receive.focus + 2) % 3;
+            Action::None
+        }
+        KeyCode::Backspace => {
+            receive_field_mut(&mut app.receive).pop();
+            Action::None
+        }
+        KeyCode::Char(c) => {
+            receive_field_mut(&mut app.receive).push(c);
+            Action::None
+        }
+        _ => Action::None,
+    }
+}
+
+fn receive_field_mut(form: &mut ReceiveForm) -> &mut String {
+    match form.focus {
+        0 => &mut form.amount,
+        1 => &mut form.label,
+        _ => &mut form.message,
+    }
+}
+
 fn field_mut(form: &mut SendForm) -> &mut String {
     match form.focus {
         0 => &mut form.recipient,
@@ -1135,11 +1869,14 @@ fn draw(f: &mut Frame<'_>, app: &App) {
         Screen::Wallets => draw_wallets(f, chunks[1], app),
         Screen::Wallet => draw_wallet(f, chunks[1], app),
         Screen::Send => draw_send(f, chunks[1], app),
+        Screen::Receive => draw_receive(f, chunks[1], app),
         Screen::Result => draw_result(f, chunks[1], app),
         Screen::Create => draw_create(f, chunks[1], app),
         Screen::Import => draw_import(f, chunks[1], app),
         Screen::Network => draw_network(f, chunks[1], app),
         Screen::Broadcast => draw_broadcast(f, chunks[1], app),
+        Screen::Logs => draw_logs(f, chunks[1], app),
+        Screen::Reorgs => draw_reorgs(f, chunks[1], app),
     }
     draw_status(f, chunks[2], app);
     draw_keys(f, chunks[3], app);
@@ -1184,7 +1921,7 @@ fn draw_shutdown_confirm(f: &mut Frame<'_>) {
 fn draw_help(f: &mut Frame<'_>) {
     let area = f.area();
     let w: u16 = 60.min(area.width.saturating_sub(4));
-    let h: u16 = 20.min(area.height.saturating_sub(2));
+    let h: u16 = 23.min(area.height.saturating_sub(2));
     let rect = Rect {
         x: area.x + area.width.saturating_sub(w) / 2,
         y: area.y + area.height.saturating_sub(h) / 2,
@@ -1200,22 +1937,88 @@ fn draw_help(f: &mut Frame<'_>) {
     let dim = Style::default().fg(Color::DarkGray);
     let lines = vec![
         Line::from(Span::styled("global", bold)),
-        Line::from(vec![Span::styled("  ? ", dim), Span::raw("help"), Span::styled("    u ", dim), Span::raw("toggle sats/BTC"), Span::styled("    Ctrl+c ", dim), Span::raw("quit")]),
+        Line::from(vec![
+            Span::styled("  ? ", dim),
+            Span::raw("help"),
+            Span::styled("    u ", dim),
+            Span::raw("toggle sats/BTC"),
+            Span::styled("    Ctrl+c ", dim),
+            Span::raw("quit"),
+        ]),
         Line::from(""),
         Line::from(Span::styled("wallets list", bold)),
-        Line::from(vec![Span::styled("  j/k ", dim), Span::raw("move    "), Span::styled("Enter ", dim), Span::raw("open    "), Span::styled("c ", dim), Span::raw("create    "), Span::styled("i ", dim), Span::raw("import")]),
-        Line::from(vec![Span::styled("  a ", dim), Span::raw("set-active    "), Span::styled("q ", dim), Span::raw("quit    "), Span::styled("X ", dim), Span::raw("shutdown daemon")]),
+        Line::from(vec![
+            Span::styled("  j/k ", dim),
+            Span::raw("move    "),
+            Span::styled("Enter ", dim),
+            Span::raw("open    "),
+            Span::styled("c ", dim),
+            Span::raw("create    "),
+            Span::styled("i ", dim),
+            Span::raw("import"),
+        ]),
+        Line::from(vec![
+            Span::styled("  a ", dim),
+            Span::raw("set-active    "),
+            Span::styled("q ", dim),
+            Span::raw("quit    "),
+            Span::styled("X ", dim),
+            Span::raw("shutdown daemon"),
+        ]),
+        Line::from(vec![
+            Span::styled("  l ", dim),
+            Span::raw("daemon logs (1-5 level, t target, f follow, g/G top/end)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  o ", dim),
+            Span::raw("chain reorgs seen by the wallets"),
+        ]),
+        Line::from(vec![
+            Span::styled("  N ", dim),
+            Span::raw("next network, when the daemon follows several"),
+        ]),
         Line::from(""),
         Line::from(Span::styled("wallet detail", bold)),
-        Line::from(vec![Span::styled("  r ", dim), Span::raw("reveal address    "), Span::styled("s ", dim), Span::raw("send")]),
-        Line::from(vec![Span::styled("  a ", dim), Span::raw("set-active        "), Span::styled("Esc ", dim), Span::raw("back")]),
+        Line::from(vec![
+            Span::styled("  r ", dim),
+            Span::raw("reveal address    "),
+            Span::styled("R ", dim),
+            Span::raw("payment request    "),
+            Span::styled("s ", dim),
+            Span::raw("send"),
+        ]),
+        Line::from(vec![
+            Span::styled("  a ", dim),
+            Span::raw("set-active        "),
+            Span::styled("Esc ", dim),
+            Span::raw("back"),
+        ]),
         Line::from(""),
         Line::from(Span::styled("forms (send / create / import)", bold)),
-        Line::from(vec![Span::styled("  Tab ", dim), Span::raw("next field    "), Span::styled("Alt+d ", dim), Span::raw("drain (send only)")]),
-        Line::from(vec![Span::styled("  Enter ", dim), Span::raw("submit    "), Span::styled("Esc ", dim), Span::raw("back")]),
+        Line::from(vec![
+            Span::styled("  Tab ", dim),
+            Span::raw("next field    "),
+            Span::styled("Alt+d ", dim),
+            Span::raw("drain (send only)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Alt+1/2/3 ", dim),
+            Span::raw("low/medium/high fee preset (send only)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Enter ", dim),
+            Span::raw("submit    "),
+            Span::styled("Esc ", dim),
+            Span::raw("back"),
+        ]),
         Line::from(""),
         Line::from(Span::styled("send result", bold)),
-        Line::from(vec![Span::styled("  b ", dim), Span::raw("broadcast (if signed)    "), Span::styled("Esc ", dim), Span::raw("back")]),
+        Line::from(vec![
+            Span::styled("  b ", dim),
+            Span::raw("broadcast (if signed)    "),
+            Span::styled("Esc ", dim),
+            Span::raw("back"),
+        ]),
     ];
     f.render_widget(Clear, rect);
     f.render_widget(block, rect);
@@ -1227,11 +2030,14 @@ fn draw_title(f: &mut Frame<'_>, area: Rect, app: &App) {
         Screen::Wallets => " kyoto-tui  wallets ",
         Screen::Wallet => " kyoto-tui  wallet ",
         Screen::Send => " kyoto-tui  send ",
+        Screen::Receive => " kyoto-tui  receive ",
         Screen::Result => " kyoto-tui  result ",
         Screen::Create => " kyoto-tui  create wallet ",
         Screen::Import => " kyoto-tui  import wallet ",
         Screen::Network => " kyoto-tui  network ",
         Screen::Broadcast => " kyoto-tui  broadcast ",
+        Screen::Logs => " kyoto-tui  logs ",
+        Screen::Reorgs => " kyoto-tui  reorgs ",
     };
     let p = Paragraph::new(Span::styled(
         label,
@@ -1246,7 +2052,7 @@ fn draw_title(f: &mut Frame<'_>, area: Rect, app: &App) {
 fn draw_wallets(f: &mut Frame<'_>, area: Rect, app: &App) {
     let rows = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(3)])
+        .constraints([Constraint::Min(0), Constraint::Length(6)])
         .split(area);
     draw_wallet_list(f, rows[0], app);
     draw_sync_gauge(f, rows[1], app);
@@ -1254,26 +2060,101 @@ fn draw_wallets(f: &mut Frame<'_>, area: Rect, app: &App) {
 
 fn draw_sync_gauge(f: &mut Frame<'_>, area: Rect, app: &App) {
     let block = Block::default().borders(Borders::ALL).title(" sync ");
-    match app.progress {
-        Some(p) => {
-            let pct = p.clamp(0.0, 100.0);
-            let gauge = Gauge::default()
-                .block(block)
-                .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
-                .ratio((pct / 100.0).into())
-                .label(format!("{pct:.1}%"));
-            f.render_widget(gauge, area);
-        }
-        None => {
-            let inner = block.inner(area);
-            f.render_widget(block, area);
-            f.render_widget(
-                Paragraph::new("(waiting for first progress event)")
-                    .style(Style::default().fg(Color::DarkGray)),
-                inner,
-            );
+    if let Some(reason) = &app.degraded {
+        let block = block.title(Span::styled(
+            " degraded ",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        f.render_widget(
+            Paragraph::new(format!(
+                "{reason}; the daemon is restarting the light client"
+            ))
+            .style(Style::default().fg(Color::Red)),
+            inner,
+        );
+        return;
+    }
+    let Some(sync) = &app.sync else {
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        f.render_widget(
+            Paragraph::new("(waiting for the daemon)").style(Style::default().fg(Color::DarkGray)),
+            inner,
+        );
+        return;
+    };
+    let mut title = String::from(" sync ");
+    if let Some(p) = app.progress {
+        title.push_str(&format!(" {:.1}% ", p.clamp(0.0, 100.0)));
+    }
+    if let Some(eta) = sync.eta_secs {
+        title.push_str(&format!(" ETA {} ", duration(eta)));
+    }
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1); 4])
+        .split(inner);
+    let dim = Style::default().fg(Color::DarkGray);
+    f.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("headers ", dim),
+            Span::raw(
+                sync.header_height
+                    .map_or("—".to_string(), |h| h.to_string()),
+            ),
+            Span::styled("   peer tip ", dim),
+            Span::raw(sync.peer_tip.map_or("—".to_string(), |h| h.to_string())),
+            Span::styled("   blocks fetched ", dim),
+            Span::raw(sync.blocks.to_string()),
+        ])),
+        rows[0],
+    );
+    match sync.stages {
+        Some((filter_headers, filters, total)) => {
+            for (row, label, done) in [
+                (rows[1], "filter headers", filter_headers),
+                (rows[2], "filters", filters),
+            ] {
+                let ratio = if total == 0 {
+                    0.0
+                } else {
+                    (f64::from(done) / f64::from(total)).clamp(0.0, 1.0)
+                };
+                f.render_widget(
+                    Gauge::default()
+                        .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+                        .ratio(ratio)
+                        .label(format!("{label} {done}/{total}")),
+                    row,
+                );
+            }
         }
+        None => f.render_widget(
+            Paragraph::new("(waiting for first progress event)").style(dim),
+            rows[1],
+        ),
     }
+    let warning = match sync.warnings.last() {
+        Some((time, message)) => Line::from(vec![
+            Span::styled(
+                format!(
+                    "{:02}:{:02}:{:02} ",
+                    time / 3600 % 24,
+                    time / 60 % 60,
+                    time % 60
+                ),
+                dim,
+            ),
+            Span::styled(message.clone(), Style::default().fg(Color::Yellow)),
+        ]),
+        None => Line::from(Span::styled("no warnings", dim)),
+    };
+    f.render_widget(Paragraph::new(warning), rows[3]);
 }
 
 fn draw_wallet_list(f: &mut Frame<'_>, area: Rect, app: &App) {
@@ -1345,17 +2226,47 @@ fn draw_wallet(f: &mut Frame<'_>, area: Rect, app: &App) {
     // Left
     let left = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .constraints([
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Min(0),
+        ])
         .split(cols[0]);
     let balance_line = match row {
         Some(r) => format!(
-            "balance: {}\nactive: {}",
+            "balance:           {}\n  confirmed:       {}\n  pending (ours):  {}\n  pending (other): {}\n  immature:        {}\nspendable:         {}\nactive: {}",
             app.unit.format(r.sats),
+            app.unit.format(r.confirmed),
+            app.unit.format(r.trusted_pending),
+            app.unit.format(r.untrusted_pending),
+            app.unit.format(r.immature),
+            app.unit.format(r.spendable),
             if r.active { "yes" } else { "no" },
         ),
         None => "(not in current balances)".to_string(),
     };
     f.render_widget(Paragraph::new(balance_line), left[0]);
+    let status_block = Block::default().borders(Borders::TOP).title(" sync ");
+    let status_para = match row.and_then(|r| r.status.as_ref()) {
+        Some(s) => {
+            let index = |i: Option<u32>| i.map_or("—".to_string(), |i| i.to_string());
+            Paragraph::new(format!(
+                "chain:    {} {}…\nscan:     {}\nrevealed: receive {} / change {}\ntxs:      {}   updated {}",
+                s.height,
+                s.hash.get(..16).unwrap_or(&s.hash),
+                match s.recovery_from {
+                    Some(h) => format!("recovery from {h}"),
+                    None => "sync".to_string(),
+                },
+                index(s.external),
+                index(s.internal),
+                s.transactions,
+                s.last_update.map_or("never".to_string(), ago),
+            ))
+        }
+        None => Paragraph::new("(loading...)"),
+    };
+    f.render_widget(status_para.block(status_block), left[1]);
     let history = app.history.as_deref().unwrap_or("(loading...)");
     let history_block = Block::default().borders(Borders::TOP).title(" history ");
     let history_para = if app.history.is_none() {
@@ -1386,25 +2297,28 @@ fn draw_wallet(f: &mut Frame<'_>, area: Rect, app: &App) {
     };
     f.render_widget(
         history_para.block(history_block).wrap(Wrap { trim: false }),
-        left[1],
+        left[2],
     );
 
     // Right
     let right = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(2)])
+        .constraints([Constraint::Min(0), Constraint::Length(3)])
         .split(cols[1]);
-    let qr_lines = match &app.receive_address {
-        Some(addr) => qr_paragraph(addr),
+    let qr_data = app.receive_uri.as_ref().or(app.receive_address.as_ref());
+    let qr_lines = match qr_data {
+        Some(data) => qr_paragraph(data),
         None => vec![Line::from(Span::styled(
             "press 'r' to reveal a new receive address",
             Style::default().fg(Color::DarkGray),
         ))],
     };
     f.render_widget(Paragraph::new(qr_lines), right[0]);
-    let addr_text = app.receive_address.as_deref().unwrap_or("");
+    let addr_text = qr_data.map(String::as_str).unwrap_or("");
     f.render_widget(
-        Paragraph::new(addr_text).style(Style::default().fg(Color::Cyan)),
+        Paragraph::new(addr_text)
+            .style(Style::default().fg(Color::Cyan))
+            .wrap(Wrap { trim: false }),
         right[1],
     );
 }
@@ -1424,17 +2338,30 @@ fn draw_send(f: &mut Frame<'_>, area: Rect, app: &App) {
             Constraint::Min(0),
         ])
         .split(inner);
-    draw_field(f, rows[0], "recipient", &app.form.recipient, app.form.focus == 0);
+    draw_field(
+        f,
+        rows[0],
+        "recipient",
+        &app.form.recipient,
+        app.form.focus == 0,
+    );
     let sats_label = if app.form.drain {
         "sats (ignored — drain mode)"
     } else {
         "sats"
     };
     draw_field(f, rows[1], sats_label, &app.form.sats, app.form.focus == 1);
+    let fee_label = match app.fees {
+        Some(p) => format!(
+            "sat/vB (default {:.1})   presets: Alt+1 low {:.1} · Alt+2 medium {:.1} · Alt+3 high {:.1}",
+            p.medium, p.low, p.medium, p.high
+        ),
+        None => "sat/vB (default 2; no fee estimates yet)".to_string(),
+    };
     draw_field(
         f,
         rows[2],
-        "sat/vB (default 2)",
+        &fee_label,
         &app.form.sat_per_vb,
         app.form.focus == 2,
     );
@@ -1445,11 +2372,67 @@ fn draw_send(f: &mut Frame<'_>, area: Rect, app: &App) {
         &app.form.out_path,
         app.form.focus == 3,
     );
-    let drain = format!(
+    let mut lines = vec![Line::from(format!(
         "drain wallet: [{}]   (alt+d to toggle)",
         if app.form.drain { "x" } else { " " }
+    ))];
+    if let Some(label) = &app.form.label {
+        lines.push(Line::from(vec![
+            Span::styled("label: ", Style::default().fg(Color::DarkGray)),
+            Span::raw(label.clone()),
+        ]));
+    }
+    f.render_widget(Paragraph::new(lines), rows[4]);
+    let hint = Paragraph::new(Line::from(Span::styled(
+        "The recipient may be a bitcoin: URI; its amount and label are filled in when you leave the field.",
+        Style::default().fg(Color::DarkGray),
+    )))
+    .wrap(Wrap { trim: false });
+    f.render_widget(hint, rows[5]);
+}
+
+fn draw_receive(f: &mut Frame<'_>, area: Rect, app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" payment request ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(2),
+            Constraint::Length(2),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+    draw_field(
+        f,
+        rows[0],
+        "amount in sats (optional)",
+        &app.receive.amount,
+        app.receive.focus == 0,
+    );
+    draw_field(
+        f,
+        rows[1],
+        "label (optional)",
+        &app.receive.label,
+        app.receive.focus == 1,
+    );
+    draw_field(
+        f,
+        rows[2],
+        "message (optional)",
+        &app.receive.message,
+        app.receive.focus == 2,
     );
-    f.render_widget(Paragraph::new(drain), rows[4]);
+    let hint = Paragraph::new(Line::from(Span::styled(
+        "Enter reveals a fresh address and shows a BIP-21 bitcoin: URI for it as a QR code.",
+        Style::default().fg(Color::DarkGray),
+    )))
+    .wrap(Wrap { trim: false });
+    f.render_widget(hint, rows[3]);
 }
 
 fn draw_field(f: &mut Frame<'_>, area: Rect, label: &str, value: &str, focused: bool) {
@@ -1472,7 +2455,9 @@ fn draw_field(f: &mut Frame<'_>, area: Rect, label: &str, value: &str, focused:
 }
 
 fn draw_result(f: &mut Frame<'_>, area: Rect, app: &App) {
-    let block = Block::default().borders(Borders::ALL).title(" send result ");
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" send result ");
     let inner = block.inner(area);
     f.render_widget(block, area);
     let Some(res) = app.result.as_ref() else {
@@ -1487,6 +2472,15 @@ fn draw_result(f: &mut Frame<'_>, area: Rect, app: &App) {
             if res.signed { "yes" } else { "no" }
         )),
     ];
+    if let Some(l) = &res.label {
+        lines.push(Line::from(format!("label:   {l}")));
+    }
+    if let Some(w) = &res.warning {
+        lines.push(Line::from(Span::styled(
+            format!("warning: {w}"),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
     if let Some(t) = &res.broadcast_txid {
         lines.push(Line::from(format!("broadcast: {t}")));
     } else if res.signed {
@@ -1502,7 +2496,10 @@ fn draw_result(f: &mut Frame<'_>, area: Rect, app: &App) {
             hex
         };
         lines.push(Line::from(""));
-        lines.push(Line::from(Span::styled("raw tx (truncated):", Style::default().fg(Color::DarkGray))));
+        lines.push(Line::from(Span::styled(
+            "raw tx (truncated):",
+            Style::default().fg(Color::DarkGray),
+        )));
         lines.push(Line::from(snippet));
     }
     f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
@@ -1528,7 +2525,16 @@ fn draw_status(f: &mut Frame<'_>, area: Rect, app: &App) {
         Span::styled(" network ", Style::default().fg(Color::DarkGray)),
         Span::styled(
             network.to_string(),
-            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            match app.networks.len() {
+                0 | 1 => String::new(),
+                n => format!(" +{} (N)", n - 1),
+            },
+            Style::default().fg(Color::DarkGray),
         ),
         Span::raw("   "),
         Span::styled("height ", Style::default().fg(Color::DarkGray)),
@@ -1540,12 +2546,32 @@ fn draw_status(f: &mut Frame<'_>, area: Rect, app: &App) {
         Span::styled("active ", Style::default().fg(Color::DarkGray)),
         Span::raw(active.to_string()),
     ];
+    if let Some(r) = app
+        .reorgs
+        .last()
+        .filter(|_| app.reorgs.len() > app.reorgs_seen)
+    {
+        let unconfirmed = r.moved.iter().filter(|m| m.3.is_none()).count();
+        spans.push(Span::raw("   "));
+        spans.push(Span::styled(
+            format!(
+                "reorg: depth {} above {}, {unconfirmed} tx unconfirmed (o)",
+                r.depth, r.common_height
+            ),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
     if let Some(err) = &app.last_error {
         spans.push(Span::raw("   "));
         spans.push(Span::styled(err.as_str(), Style::default().fg(Color::Red)));
     } else if let Some(info) = &app.last_info {
         spans.push(Span::raw("   "));
-        spans.push(Span::styled(info.as_str(), Style::default().fg(Color::Green)));
+        spans.push(Span::styled(
+            info.as_str(),
+            Style::default().fg(Color::Green),
+        ));
     }
     f.render_widget(Paragraph::new(Line::from(spans)), area);
 }
@@ -1553,43 +2579,119 @@ fn draw_status(f: &mut Frame<'_>, area: Rect, app: &App) {
 fn draw_keys(f: &mut Frame<'_>, area: Rect, app: &App) {
     let mut spans = match app.screen() {
         Screen::Wallets => vec![
-            key(" j/k "), text("move "), key("Enter "), text("open "),
-            key("c "), text("create "), key("i "), text("import "),
-            key("a "), text("set-active "), key("n "), text("network "),
-            key("b "), text("broadcast "),
-            key("X "), text("shutdown "), key("q "), text("quit"),
+            key(" j/k "),
+            text("move "),
+            key("Enter "),
+            text("open "),
+            key("c "),
+            text("create "),
+            key("i "),
+            text("import "),
+            key("a "),
+            text("set-active "),
+            key("n "),
+            text("network "),
+            key("b "),
+            text("broadcast "),
+            key("l "),
+            text("logs "),
+            key("o "),
+            text("reorgs "),
+            key("X "),
+            text("shutdown "),
+            key("q "),
+            text("quit"),
         ],
         Screen::Wallet => vec![
-            key(" r "), text("reveal "), key("s "), text("send "),
-            key("a "), text("set-active "), key("Esc "), text("back"),
+            key(" r "),
+            text("reveal "),
+            key("R "),
+            text("request "),
+            key("s "),
+            text("send "),
+            key("a "),
+            text("set-active "),
+            key("Esc "),
+            text("back"),
         ],
         Screen::Send => vec![
-            key(" Tab "), text("next field "), key("Alt+d "), text("toggle drain "),
-            key("Enter "), text("submit "), key("Esc "), text("back"),
+            key(" Tab "),
+            text("next field "),
+            key("Alt+d "),
+            text("toggle drain "),
+            key("Alt+1/2/3 "),
+            text("fee preset "),
+            key("Enter "),
+            text("submit "),
+            key("Esc "),
+            text("back"),
         ],
         Screen::Result => vec![
-            key(" b "), text("broadcast (if signed) "), key("Esc "), text("back"),
+            key(" b "),
+            text("broadcast (if signed) "),
+            key("Esc "),
+            text("back"),
         ],
         Screen::Create => vec![
-            key(" Tab "), text("next field "), key("Enter "), text("submit "),
-            key("Esc "), text("back"),
+            key(" Tab "),
+            text("next field "),
+            key("Enter "),
+            text("submit "),
+            key("Esc "),
+            text("back"),
         ],
-        Screen::Import => vec![
-            key(" Enter "), text("submit "), key("Esc "), text("back"),
+        Screen::Import => vec![key(" Enter "), text("submit "), key("Esc "), text("back")],
+        Screen::Receive => vec![
+            key(" Tab "),
+            text("next field "),
+            key("Enter "),
+            text("reveal "),
+            key("Esc "),
+            text("back"),
         ],
         Screen::Network => vec![
-            key(" +/- "), text("required peers "), key("Tab "), text("next field "),
-            key("Enter "), text("add peer "), key("Esc "), text("back"),
+            key(" +/- "),
+            text("required peers "),
+            key("Tab "),
+            text("next field "),
+            key("Enter "),
+            text("add peer "),
+            key("Esc "),
+            text("back"),
         ],
         Screen::Broadcast => vec![
-            key(" Enter "), text("broadcast "), key("Alt+f "), text("toggle finalize "),
-            key("Esc "), text("back"),
+            key(" Enter "),
+            text("broadcast "),
+            key("Alt+f "),
+            text("toggle finalize "),
+            key("Esc "),
+            text("back"),
+        ],
+        Screen::Logs => vec![
+            key(" j/k/PgUp/PgDn "),
+            text("scroll "),
+            key("f "),
+            text("follow "),
+            key("1-5 "),
+            text("level "),
+            key("t "),
+            text("target "),
+            key("c "),
+            text("clear "),
+            key("Esc "),
+            text("back"),
         ],
+        Screen::Reorgs => vec![key(" Esc "), text("back")],
     };
     spans.push(text("   "));
     if !matches!(
         app.screen(),
-        Screen::Send | Screen::Create | Screen::Import | Screen::Network | Screen::Broadcast
+        Screen::Send
+            | Screen::Create
+            | Screen::Import
+            | Screen::Network
+            | Screen::Broadcast
+            | Screen::Receive
     ) {
         spans.push(key("u "));
         spans.push(text(match app.unit {
@@ -1609,6 +2711,22 @@ fn text(s: &str) -> Span<'_> {
     Span::raw(s.to_string())
 }
 
+fn duration(secs: u64) -> String {
+    match secs {
+        s if s < 60 => "<1m".to_string(),
+        s if s < 3600 => format!("{}m", s / 60),
+        s => format!("{}h {:02}m", s / 3600, s / 60 % 60),
+    }
+}
+
+fn ago(unix: u64) -> String {
+    match clock::unix_now().saturating_sub(unix) {
+        s if s < 60 => format!("{s}s ago"),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s => format!("{}h ago", s / 3600),
+    }
+}
+
 fn pad_right(s: &str, width: usize) -> String {
     if s.chars().count() >= width {
         s.to_string()
@@ -1623,8 +2741,141 @@ fn pad_right(s: &str, width: usize) -> String {
     }
 }
 
+fn draw_logs(f: &mut Frame<'_>, area: Rect, app: &App) {
+    let logs = &app.logs;
+    let mut title = format!(
+        " logs  {}+  {} ",
+        logs.level,
+        LOG_TARGETS[logs.target].unwrap_or("all targets")
+    );
+    title.push_str(if logs.follow {
+        " following "
+    } else {
+        " paused "
+    });
+    if logs.missed > 0 {
+        title.push_str(&format!(" {} dropped ", logs.missed));
+    }
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let visible = logs.visible();
+    if visible.is_empty() {
+        let msg = if logs.tail.is_some() {
+            "(no lines at this level yet)"
+        } else {
+            "(not tailing — see status bar)"
+        };
+        f.render_widget(
+            Paragraph::new(msg).style(Style::default().fg(Color::DarkGray)),
+            inner,
+        );
+        return;
+    }
+    let height = inner.height as usize;
+    let scroll = logs.scroll.min(visible.len().saturating_sub(height));
+    let end = visible.len() - scroll;
+    let start = end.saturating_sub(height);
+    let dim = Style::default().fg(Color::DarkGray);
+    let lines: Vec<Line> = visible[start..end]
+        .iter()
+        .map(|e| {
+            let secs = e.time_ms / 1000;
+            let color = match e.level {
+                tracing::Level::ERROR => Color::Red,
+                tracing::Level::WARN => Color::Yellow,
+                tracing::Level::INFO => Color::Green,
+                tracing::Level::DEBUG => Color::Cyan,
+                _ => Color::DarkGray,
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!(
+                        "{:02}:{:02}:{:02}.{:03} ",
+                        secs / 3600 % 24,
+                        secs / 60 % 60,
+                        secs % 60,
+                        e.time_ms % 1000
+                    ),
+                    dim,
+                ),
+                Span::styled(
+                    format!("{:>5} ", e.level.as_str()),
+                    Style::default().fg(color),
+                ),
+                Span::styled(format!("{}: ", e.target), dim),
+                Span::raw(e.message.clone()),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn draw_reorgs(f: &mut Frame<'_>, area: Rect, app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" reorgs  {} since daemon start ", app.reorgs.len()));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if app.reorgs.is_empty() {
+        f.render_widget(
+            Paragraph::new("(none seen)").style(Style::default().fg(Color::DarkGray)),
+            inner,
+        );
+        return;
+    }
+    let dim = Style::default().fg(Color::DarkGray);
+    let short = |h: &str| h.get(..16).unwrap_or(h).to_string();
+    let mut lines = Vec::new();
+    for r in app.reorgs.iter().rev() {
+        let secs = r.time;
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!(
+                    "{:02}:{:02}:{:02} ",
+                    secs / 3600 % 24,
+                    secs / 60 % 60,
+                    secs % 60
+                ),
+                dim,
+            ),
+            Span::styled(
+                format!("depth {} ", r.depth),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled("above ", dim),
+            Span::raw(format!("{}  ", r.common_height)),
+            Span::raw(format!(
+                "{} {}… → {} {}…",
+                r.old_tip.0,
+                short(&r.old_tip.1),
+                r.new_tip.0,
+                short(&r.new_tip.1)
+            )),
+            Span::styled(format!("  {}", r.wallets.join(", ")), dim),
+        ]));
+        for (wallet, txid, from, to) in &r.moved {
+            let to = match to {
+                Some(h) => Span::raw(format!("block {h}")),
+                None => Span::styled("unconfirmed", Style::default().fg(Color::Red)),
+            };
+            lines.push(Line::from(vec![
+                Span::raw("    "),
+                Span::styled(format!("{wallet} "), dim),
+                Span::raw(format!("{txid}  block {from} → ")),
+                to,
+            ]));
+        }
+    }
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
 fn draw_create(f: &mut Frame<'_>, area: Rect, app: &App) {
-    let block = Block::default().borders(Borders::ALL).title(" create wallet ");
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" create wallet ");
     let inner = block.inner(area);
     f.render_widget(block, area);
     let rows = Layout::default()
@@ -1692,7 +2943,8 @@ fn draw_network(f: &mut Frame<'_>, area: Rect, app: &App) {
         .peer_count
         .map(|n| n.to_string())
         .unwrap_or_else(|| "—".into());
-    let summary = vec![
+    let summary =
+        vec![
         Line::from(vec![
             Span::styled("required peers: ", Style::default().fg(Color::DarkGray)),
             Span::styled(required, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
@@ -1707,7 +2959,13 @@ fn draw_network(f: &mut Frame<'_>, area: Rect, app: &App) {
     ];
     f.render_widget(Paragraph::new(summary).wrap(Wrap { trim: false }), rows[0]);
 
-    draw_field(f, rows[1], "peer ip", &app.network.ip, app.network.focus == 0);
+    draw_field(
+        f,
+        rows[1],
+        "peer ip",
+        &app.network.ip,
+        app.network.focus == 0,
+    );
     draw_field(
         f,
         rows[2],
@@ -1720,7 +2978,13 @@ fn draw_network(f: &mut Frame<'_>, area: Rect, app: &App) {
         "tor socks5 proxy ip   [{}]   (Alt+t to toggle)",
         if app.network.tor_enabled { "on" } else { "off" }
     );
-    draw_field(f, rows[3], &tor_label, &app.network.tor_ip, app.network.focus == 2);
+    draw_field(
+        f,
+        rows[3],
+        &tor_label,
+        &app.network.tor_ip,
+        app.network.focus == 2,
+    );
     draw_field(
         f,
         rows[4],
@@ -1744,7 +3008,9 @@ fn draw_network(f: &mut Frame<'_>, area: Rect, app: &App) {
 }
 
 fn draw_import(f: &mut Frame<'_>, area: Rect, app: &App) {
-    let block = Block::default().borders(Borders::ALL).title(" import wallet ");
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" import wallet ");
     let inner = block.inner(area);
     f.render_widget(block, area);
     let rows = Layout::default()
@@ -1760,7 +3026,9 @@ fn draw_import(f: &mut Frame<'_>, area: Rect, app: &App) {
 }
 
 fn draw_broadcast(f: &mut Frame<'_>, area: Rect, app: &App) {
-    let block = Block::default().borders(Borders::ALL).title(" broadcast psbt ");
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" broadcast psbt ");
     let inner = block.inner(area);
     f.render_widget(block, area);
     let rows = Layout::default()
@@ -1783,7 +3051,11 @@ fn draw_broadcast(f: &mut Frame<'_>, area: Rect, app: &App) {
             Span::styled("finalize: ", Style::default().fg(Color::DarkGray)),
             Span::styled(
                 if app.broadcast.finalize { "on" } else { "off" },
-                Style::default().fg(if app.broadcast.finalize { Color::Green } else { Color::DarkGray }),
+                Style::default().fg(if app.broadcast.finalize {
+                    Color::Green
+                } else {
+                    Color::DarkGray
+                }),
             ),
             Span::styled("   (Alt+f to toggle)", Style::default().fg(Color::DarkGray)),
         ]),
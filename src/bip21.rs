@@ -0,0 +1,301 @@
+use std::str::FromStr;
+
+use bdk_wallet::bitcoin::{Address, Amount, Denomination, Network};
+
+const SCHEME: &str = "bitcoin:";
+
+#[derive(Debug)]
+pub enum UriError {
+    Scheme,
+    Address(String),
+    Network(String),
+    Amount(String),
+    Encoding(String),
+    Duplicate(String),
+    RequiredParam(String),
+}
+
+impl std::fmt::Display for UriError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scheme => write!(f, "not a bitcoin: URI"),
+            Self::Address(m) => write!(f, "address: {m}"),
+            Self::Network(m) => write!(f, "address network: {m}"),
+            Self::Amount(m) => write!(f, "amount: {m}"),
+            Self::Encoding(m) => write!(f, "encoding: {m}"),
+            Self::Duplicate(k) => write!(f, "parameter `{k}` given more than once"),
+            Self::RequiredParam(k) => write!(f, "unsupported required parameter `{k}`"),
+        }
+    }
+}
+
+impl std::error::Error for UriError {}
+
+/// A parsed BIP-21 payment request.
+pub struct PaymentUri {
+    pub address: Address,
+    pub amount: Option<Amount>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+pub fn is_uri(s: &str) -> bool {
+    s.get(..SCHEME.len())
+        .is_some_and(|p| p.eq_ignore_ascii_case(SCHEME))
+}
+
+/// Parse a `bitcoin:` URI, rejecting addresses for another network and any
+/// `req-` parameter we don't understand (which is all of them).
+pub fn parse(uri: &str, network: Network) -> Result<PaymentUri, UriError> {
+    if !is_uri(uri) {
+        return Err(UriError::Scheme);
+    }
+    let rest = &uri[SCHEME.len()..];
+    let (addr, query) = match rest.split_once('?') {
+        Some((a, q)) => (a, Some(q)),
+        None => (rest, None),
+    };
+    let address = Address::from_str(addr)
+        .map_err(|e| UriError::Address(e.to_string()))?
+        .require_network(network)
+        .map_err(|e| UriError::Network(e.to_string()))?;
+
+    let mut out = PaymentUri {
+        address,
+        amount: None,
+        label: None,
+        message: None,
+    };
+    for pair in query.unwrap_or("").split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        match key {
+            "amount" => {
+                if out.amount.is_some() {
+                    return Err(UriError::Duplicate(key.into()));
+                }
+                let amt = Amount::from_str_in(&value, Denomination::Bitcoin)
+                    .map_err(|e| UriError::Amount(e.to_string()))?;
+                out.amount = Some(amt);
+            }
+            "label" => {
+                if out.label.replace(value).is_some() {
+                    return Err(UriError::Duplicate(key.into()));
+                }
+            }
+            "message" => {
+                if out.message.replace(value).is_some() {
+                    return Err(UriError::Duplicate(key.into()));
+                }
+            }
+            k if k.starts_with("req-") => return Err(UriError::RequiredParam(k.into())),
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Build a `bitcoin:` URI. Empty labels and messages are omitted.
+pub fn build(
+    address: &Address,
+    amount: Option<Amount>,
+    label: Option<&str>,
+    message: Option<&str>,
+) -> String {
+    let mut params = Vec::new();
+    if let Some(a) = amount {
+        params.push(format!("amount={}", format_btc(a)));
+    }
+    if let Some(l) = label.filter(|l| !l.is_empty()) {
+        params.push(format!("label={}", percent_encode(l)));
+    }
+    if let Some(m) = message.filter(|m| !m.is_empty()) {
+        params.push(format!("message={}", percent_encode(m)));
+    }
+    if params.is_empty() {
+        format!("{SCHEME}{address}")
+    } else {
+        format!("{SCHEME}{address}?{}", params.join("&"))
+    }
+}
+
+fn format_btc(amount: Amount) -> String {
+    let sats = amount.to_sat();
+    let whole = sats / 100_000_000;
+    let frac = sats % 100_000_000;
+    if frac == 0 {
+        whole.to_string()
+    } else {
+        let frac = format!("{frac:08}");
+        format!("{whole}.{}", frac.trim_end_matches('0'))
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> Result<String, UriError> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .ok_or_else(|| UriError::Encoding(format!("truncated escape in `{s}`")))?;
+            // Not `u8::from_str_radix`, which would take a sign.
+            match (hex_digit(hex[0]), hex_digit(hex[1])) {
+                (Some(hi), Some(lo)) => out.push((hi << 4) | lo),
+                _ => {
+                    let hex = String::from_utf8_lossy(hex);
+                    return Err(UriError::Encoding(format!("bad escape `%{hex}`")));
+                }
+            }
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|e| UriError::Encoding(e.to_string()))
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin::Script;
+
+    fn address(network: Network) -> Address {
+        Address::p2wsh(Script::new(), network)
+    }
+
+    fn uri(query: &str) -> String {
+        format!("bitcoin:{}?{query}", address(Network::Bitcoin))
+    }
+
+    #[test]
+    fn parses_parameters() {
+        let p = parse(
+            &uri("amount=0.5&label=Luke%20Jr&message=Donation&x=ignored"),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(p.address, address(Network::Bitcoin));
+        assert_eq!(p.amount, Some(Amount::from_sat(50_000_000)));
+        assert_eq!(p.label.as_deref(), Some("Luke Jr"));
+        assert_eq!(p.message.as_deref(), Some("Donation"));
+    }
+
+    #[test]
+    fn scheme_is_case_insensitive() {
+        let addr = address(Network::Bitcoin);
+        assert!(parse(&format!("BitCoin:{addr}"), Network::Bitcoin).is_ok());
+        assert!(parse(&format!("BITCOIN:{addr}?amount=1"), Network::Bitcoin).is_ok());
+        assert!(matches!(
+            parse(&format!("bitcoin{addr}"), Network::Bitcoin),
+            Err(UriError::Scheme)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_network() {
+        let addr = address(Network::Testnet);
+        assert!(matches!(
+            parse(&format!("bitcoin:{addr}"), Network::Bitcoin),
+            Err(UriError::Network(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        for query in [
+            "amount=1&amount=2",
+            "label=a&label=b",
+            "message=a&message=a",
+        ] {
+            assert!(
+                matches!(
+                    parse(&uri(query), Network::Bitcoin),
+                    Err(UriError::Duplicate(_))
+                ),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_required_parameters() {
+        match parse(&uri("amount=1&req-somethingnew=1"), Network::Bitcoin) {
+            Err(UriError::RequiredParam(k)) => assert_eq!(k, "req-somethingnew"),
+            _ => panic!("accepted an unknown req- parameter"),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        for label in ["%", "%4", "ab%4", "%+1", "%-1", "%g0", "%0g"] {
+            assert!(
+                matches!(
+                    parse(&uri(&format!("label={label}")), Network::Bitcoin),
+                    Err(UriError::Encoding(_))
+                ),
+                "{label}"
+            );
+        }
+        assert_eq!(percent_decode("%41%2b%2B").unwrap(), "A++");
+    }
+
+    #[test]
+    fn format_btc_round_trips() {
+        for sats in [
+            0,
+            1,
+            10,
+            12_345_678,
+            100_000_000,
+            150_000_000,
+            2_100_000_000_000_000,
+        ] {
+            let amount = Amount::from_sat(sats);
+            let text = format_btc(amount);
+            assert_eq!(
+                Amount::from_str_in(&text, Denomination::Bitcoin).unwrap(),
+                amount,
+                "{text}"
+            );
+        }
+        assert_eq!(format_btc(Amount::from_sat(150_000_000)), "1.5");
+    }
+
+    #[test]
+    fn build_round_trips() {
+        let addr = address(Network::Bitcoin);
+        let text = build(
+            &addr,
+            Some(Amount::from_sat(1)),
+            Some("a b&c=d"),
+            Some("ünï"),
+        );
+        let p = parse(&text, Network::Bitcoin).unwrap();
+        assert_eq!(p.amount, Some(Amount::from_sat(1)));
+        assert_eq!(p.label.as_deref(), Some("a b&c=d"));
+        assert_eq!(p.message.as_deref(), Some("ünï"));
+        assert_eq!(
+            build(&addr, None, Some(""), None),
+            format!("bitcoin:{addr}")
+        );
+    }
+}
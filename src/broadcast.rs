@@ -0,0 +1,284 @@
+//! How our own transactions reach the network. By default they go to the
+//! light client's peers, which also serve our filter traffic and so can tie
+//! a broadcast to the wallets that asked for those filters. The other routes
+//! open a separate, short-lived connection for each broadcast instead.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::{Network, Transaction, Txid};
+use tracing::{info, warn};
+
+use crate::clock::unix_now;
+use crate::ipc::RequesterSlot;
+use crate::metrics::{SharedMetrics, Source};
+use crate::peer::{self, Peer};
+use crate::random;
+use crate::sync::{self, TorProxy};
+
+pub type BroadcastHistory = Arc<Mutex<Broadcasts>>;
+
+const KEEP: usize = 100;
+// Covers building a Tor circuit as well as the exchange itself.
+const SEND_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Route {
+    /// The light client's peers.
+    Node,
+    /// A new connection to a peer a DNS seed hands out.
+    Isolated,
+    /// A new connection to this `host[:port]`, which may be an onion address.
+    Peer(String),
+}
+
+impl Route {
+    pub fn parse(name: &str, peer: Option<&str>) -> Result<Self, String> {
+        match (name, peer) {
+            ("node", _) => Ok(Self::Node),
+            ("isolated", _) => Ok(Self::Isolated),
+            ("peer", Some(spec)) if !spec.trim().is_empty() => {
+                Ok(Self::Peer(spec.trim().to_string()))
+            }
+            ("peer", _) => Err("broadcast_policy peer needs broadcast_peer".into()),
+            (other, _) => Err(format!(
+                "broadcast_policy must be node, isolated or peer, not {other:?}"
+            )),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Node => "light client peers".into(),
+            Self::Isolated => "a separate connection".into(),
+            Self::Peer(spec) => spec.clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub route: Route,
+    /// Each broadcast waits a random time of up to this long.
+    pub max_delay: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            route: Route::Node,
+            max_delay: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Broadcast {
+    pub time: u64,
+    pub txid: Txid,
+    pub source: Source,
+    /// "light client peers", or the `host:port` that was connected to.
+    pub via: String,
+    pub delay_secs: u64,
+    pub error: Option<String>,
+}
+
+/// The last `KEEP` broadcasts, oldest first.
+#[derive(Default)]
+pub struct Broadcasts {
+    entries: VecDeque<Broadcast>,
+}
+
+impl Broadcasts {
+    pub fn new() -> BroadcastHistory {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    fn record(&mut self, entry: Broadcast) {
+        if self.entries.len() == KEEP {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Broadcast> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Cheap to clone; clones share the policy and history.
+#[derive(Clone)]
+pub struct Broadcaster {
+    network: Network,
+    policy: Arc<Mutex<Policy>>,
+    requester: RequesterSlot,
+    proxy: TorProxy,
+    history: BroadcastHistory,
+    metrics: SharedMetrics,
+}
+
+impl Broadcaster {
+    pub fn new(
+        network: Network,
+        policy: Policy,
+        requester: RequesterSlot,
+        proxy: TorProxy,
+        metrics: SharedMetrics,
+    ) -> Self {
+        Self {
+            network,
+            policy: Arc::new(Mutex::new(policy)),
+            requester,
+            proxy,
+            history: Broadcasts::new(),
+            metrics,
+        }
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy.lock().unwrap().clone()
+    }
+
+    pub fn set_policy(&self, policy: Policy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    pub fn history(&self) -> &BroadcastHistory {
+        &self.history
+    }
+
+    /// Whether a send from `source` could go anywhere right now. Only the
+    /// `node` route needs the light client.
+    pub fn ready(&self, source: Source) -> bool {
+        self.route(source) != Route::Node || self.requester.lock().unwrap().is_some()
+    }
+
+    /// The policy's route, except that rebroadcasts go over a separate
+    /// connection wherever there are seeds to find a peer with. Sending them
+    /// to the light client's peers again would tie the transaction to our
+    /// session however the first broadcast went.
+    fn route(&self, source: Source) -> Route {
+        match (source, self.policy().route) {
+            (Source::Rebroadcast, Route::Node) if !peer::seeds(self.network).is_empty() => {
+                Route::Isolated
+            }
+            (_, route) => route,
+        }
+    }
+
+    /// A random wait up to the policy's `max_delay`.
+    pub fn delay(&self) -> Duration {
+        let max = self.policy().max_delay.as_millis() as u64;
+        if max == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(random::below(max + 1))
+    }
+
+    /// Send `tx` by the current route, record how it went and return where
+    /// it went. `delay` is only recorded; the caller has already waited.
+    pub async fn send(
+        &self,
+        tx: &Transaction,
+        source: Source,
+        delay: Duration,
+    ) -> Result<String, String> {
+        let txid = tx.compute_txid();
+        let (via, result) = match self.route(source) {
+            Route::Node => ("light client peers".to_string(), self.via_node(tx).await),
+            Route::Isolated => match peer::seed_host(self.network) {
+                Some(seed) => match self.seed_peer(seed).await {
+                    Ok(addr) => {
+                        let result = self
+                            .via_connection(&addr.ip().to_string(), addr.port(), tx)
+                            .await;
+                        (format!("{addr} from {seed}"), result)
+                    }
+                    Err(e) => (seed.to_string(), Err(e)),
+                },
+                None => (
+                    "none".to_string(),
+                    Err(format!(
+                        "no DNS seeds for {}; set broadcast_peer",
+                        self.network
+                    )),
+                ),
+            },
+            Route::Peer(spec) => match peer::split_host(&spec, sync::default_port(self.network)) {
+                Ok((host, port)) => {
+                    let result = self.via_connection(&host, port, tx).await;
+                    (spec, result)
+                }
+                Err(e) => (spec, Err(e)),
+            },
+        };
+        self.metrics.broadcast(self.network, source, result.is_ok());
+        let what = match source {
+            Source::Ipc => "broadcast",
+            Source::Rebroadcast => "rebroadcast",
+        };
+        match &result {
+            Ok(()) => info!(target: "node", "{what} {txid} via {via}"),
+            Err(e) => warn!(target: "node", "{what} {txid} via {via}: {e}"),
+        }
+        self.history.lock().unwrap().record(Broadcast {
+            time: unix_now(),
+            txid,
+            source,
+            via: via.clone(),
+            delay_secs: delay.as_secs(),
+            error: result.as_ref().err().cloned(),
+        });
+        result.map(|()| via)
+    }
+
+    async fn via_node(&self, tx: &Transaction) -> Result<(), String> {
+        let req = self
+            .requester
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or("no wallets loaded; node is not running")?;
+        req.submit_package(tx.clone())
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    // One of the peers `seed` lists, looked up the way the connection to it
+    // will be made.
+    async fn seed_peer(&self, seed: &str) -> Result<SocketAddr, String> {
+        let proxy = *self.proxy.lock().unwrap();
+        let port = sync::default_port(self.network);
+        match tokio::time::timeout(SEND_TIMEOUT, peer::resolve(proxy, seed, port)).await {
+            Ok(Ok(addrs)) => random::pick(&addrs)
+                .copied()
+                .ok_or_else(|| format!("{seed} listed no peers")),
+            Ok(Err(e)) => Err(format!("resolve {seed}: {e}")),
+            Err(_) => Err(format!("resolve {seed}: timed out")),
+        }
+    }
+
+    async fn via_connection(&self, host: &str, port: u16, tx: &Transaction) -> Result<(), String> {
+        let proxy = *self.proxy.lock().unwrap();
+        let send = async {
+            let mut peer = Peer::connect(self.network, proxy, host, port).await?;
+            peer.send_tx(tx).await
+        };
+        match tokio::time::timeout(SEND_TIMEOUT, send).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("timed out after {}s", SEND_TIMEOUT.as_secs())),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! One network the daemon follows. The config's `network` comes first and
+//! keeps the top of the datadir; each of `extra_networks` gets its own
+//! subdirectory, wallets, light client and queues.
+
+use std::sync::{Arc, Mutex};
+
+use bdk_wallet::bitcoin::Network;
+
+use crate::broadcast::Broadcaster;
+use crate::fees::FeeCache;
+use crate::hooks::Hooks;
+use crate::ipc::RequesterSlot;
+use crate::paths::Layout;
+use crate::rebroadcast::PendingQueue;
+use crate::reorg::ReorgHistory;
+use crate::sync::{ProgressSlot, SyncDetails, SyncHealth, TrustedPeers};
+use crate::wallet::State;
+use crate::webhooks::WebhookQueue;
+
+/// Every enabled network, the primary one first.
+pub type Chains = Arc<Vec<Chain>>;
+
+/// Cheap to clone; clones share everything.
+#[derive(Clone)]
+pub struct Chain {
+    pub network: Network,
+    pub layout: Arc<Layout>,
+    pub state: Arc<Mutex<State>>,
+    pub requester: RequesterSlot,
+    pub progress: ProgressSlot,
+    pub details: SyncDetails,
+    pub health: SyncHealth,
+    pub trusted_peers: TrustedPeers,
+    pub fees: FeeCache,
+    pub pending: PendingQueue,
+    pub hooks: Hooks,
+    pub webhooks: WebhookQueue,
+    pub reorgs: ReorgHistory,
+    pub broadcaster: Broadcaster,
+}
+
+impl Chain {
+    pub fn running(&self) -> bool {
+        self.requester.lock().unwrap().is_some()
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use bdk_kyoto::HashCheckpoint;
+use bdk_wallet::bitcoin::{BlockHash, Network};
+
+// Well-known block hashes per network, excluding genesis (starting there is
+// no different from a plain sync). Entries must stay sorted by height.
+// These only need to be good enough to skip the bulk of the chain for a
+// wallet with a recent birthday; users can extend the table with a
+// checkpoints file (see `CheckpointFile`).
+#[rustfmt::skip]
+const BITCOIN: &[(u32, &str)] = &[
+    (11_111, "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"),
+    (33_333, "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6"),
+    (74_000, "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20"),
+    (105_000, "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97"),
+    (134_444, "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe"),
+    (168_000, "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763"),
+    (193_000, "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317"),
+    (210_000, "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"),
+    (216_116, "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e"),
+    (225_430, "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932"),
+    (250_000, "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214"),
+    (279_000, "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40"),
+    (295_000, "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983"),
+    (420_000, "000000000000000002cce816c0ab2c5c269cb081896b7dcb34b8422d6b74ffa1"),
+    (481_824, "0000000000000000001c8018d9cb3b742ef25114f27563e3fc4a1902167f9893"),
+    (630_000, "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d"),
+    (709_632, "0000000000000000000687bca986194dc2c1f949318629b44bb54ec0a94d8244"),
+    (840_000, "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5"),
+];
+
+const SIGNET: &[(u32, &str)] = &[(
+    170_000,
+    "00000041c812a89f084f633e4cf47e819a2f6b1c0a15162355a930410522c99d",
+)];
+
+// The blocks Bitcoin Core's testnet4 parameters use for assumevalid and
+// assumeutxo.
+#[rustfmt::skip]
+const TESTNET4: &[(u32, &str)] = &[
+    (39_550, "000000005be348057db991fa5d89fe7c4695b667cfb311391a8db374b6f681fd"),
+    (90_000, "0000000002ebe8bcda020e0dd6ccfbdfac531d2f6a81457191b99fc2df2dbe3b"),
+];
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io: {e}"),
+            Self::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// A user-supplied checkpoints file. Each non-empty line is
+/// `[<network>] <height> <block hash>`; `#` starts a comment. Lines without a
+/// network are the primary network's.
+pub struct CheckpointFile {
+    entries: Vec<(Option<Network>, u32, BlockHash)>,
+}
+
+impl CheckpointFile {
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        Self::parse(&fs::read_to_string(path).map_err(CheckpointError::Io)?)
+    }
+
+    /// All or nothing: one bad line rejects the whole file.
+    fn parse(text: &str) -> Result<Self, CheckpointError> {
+        let mut entries = Vec::new();
+        for (i, raw) in text.lines().enumerate() {
+            let line = raw.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parse_err = |message: String| CheckpointError::Parse {
+                line: i + 1,
+                message,
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (network, height, hash) = match fields[..] {
+                [height, hash] => (None, height, hash),
+                [network, height, hash] => {
+                    let network = Network::from_str(network)
+                        .map_err(|e| parse_err(format!("network: {e}")))?;
+                    (Some(network), height, hash)
+                }
+                _ => return Err(parse_err("expected `[<network>] <height> <hash>`".into())),
+            };
+            let height: u32 = height
+                .parse()
+                .map_err(|e| parse_err(format!("height: {e}")))?;
+            let hash = BlockHash::from_str(hash).map_err(|e| parse_err(format!("hash: {e}")))?;
+            entries.push((network, height, hash));
+        }
+        Ok(Self { entries })
+    }
+}
+
+pub struct Checkpoints {
+    // Sorted by height, no duplicate heights.
+    entries: Vec<(u32, BlockHash)>,
+}
+
+impl Checkpoints {
+    pub fn builtin(network: Network) -> Self {
+        let table = match network {
+            Network::Bitcoin => BITCOIN,
+            Network::Signet => SIGNET,
+            Network::Testnet4 => TESTNET4,
+            _ => &[],
+        };
+        let entries = table
+            .iter()
+            .map(|(h, hash)| {
+                let hash = BlockHash::from_str(hash).expect("built-in checkpoint hash is valid");
+                (*h, hash)
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Merge in the file's entries for `network`, and those without a
+    /// network when it is the `primary` one. A file entry replaces a built-in
+    /// one at the same height. Returns how many were merged.
+    pub fn merge(&mut self, file: &CheckpointFile, network: Network, primary: bool) -> usize {
+        let mut added = 0;
+        for (n, height, hash) in &file.entries {
+            if n.map_or(primary, |n| n == network) {
+                self.insert(*height, *hash);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    fn insert(&mut self, height: u32, hash: BlockHash) {
+        match self.entries.binary_search_by_key(&height, |(h, _)| *h) {
+            Ok(i) => self.entries[i].1 = hash,
+            Err(i) => self.entries.insert(i, (height, hash)),
+        }
+    }
+
+    /// The highest checkpoint at or below `height`, if any.
+    pub fn at_or_below(&self, height: u32) -> Option<HashCheckpoint> {
+        let idx = self.entries.partition_point(|(h, _)| *h <= height);
+        let (h, hash) = *self.entries.get(idx.checked_sub(1)?)?;
+        Some(HashCheckpoint::new(h, hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "00000000000000000000000000000000000000000000000000000000000000aa";
+    const B: &str = "00000000000000000000000000000000000000000000000000000000000000bb";
+
+    fn parse_line_error(text: &str) -> usize {
+        match CheckpointFile::parse(text) {
+            Err(CheckpointError::Parse { line, .. }) => line,
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("accepted {text:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse_line_error(&format!("# ok\n\n100 {A}\n200\n")), 4);
+        assert_eq!(parse_line_error(&format!("-1 {A}")), 1);
+        assert_eq!(parse_line_error("100 not-a-hash"), 1);
+        assert_eq!(parse_line_error(&format!("100 {A} extra field")), 1);
+        assert_eq!(parse_line_error(&format!("nonet 100 {A}")), 1);
+    }
+
+    #[test]
+    fn merges_entries_by_network() {
+        let file = CheckpointFile::parse(&format!(
+            "100 {A} # primary\nsignet 200 {B}\n  \ntestnet4 300 {A}\n"
+        ))
+        .unwrap();
+
+        let mut primary = Checkpoints::builtin(Network::Regtest);
+        assert_eq!(primary.merge(&file, Network::Regtest, true), 1);
+        assert_eq!(primary.len(), 1);
+
+        let mut signet = Checkpoints::builtin(Network::Signet);
+        let builtin = signet.len();
+        assert_eq!(signet.merge(&file, Network::Signet, false), 1);
+        assert_eq!(signet.len(), builtin + 1);
+    }
+
+    #[test]
+    fn file_entry_replaces_builtin_at_same_height() {
+        let mut cps = Checkpoints::builtin(Network::Signet);
+        let builtin = cps.len();
+        let file = CheckpointFile::parse(&format!("170000 {A}")).unwrap();
+        cps.merge(&file, Network::Signet, true);
+        assert_eq!(cps.len(), builtin);
+        let cp = cps.at_or_below(170_000).unwrap();
+        assert_eq!(cp.hash, BlockHash::from_str(A).unwrap());
+    }
+
+    #[test]
+    fn picks_highest_checkpoint_at_or_below() {
+        let mut cps = Checkpoints::builtin(Network::Regtest);
+        let file = CheckpointFile::parse(&format!("100 {A}\n200 {B}")).unwrap();
+        cps.merge(&file, Network::Regtest, true);
+
+        assert!(cps.at_or_below(0).is_none());
+        assert!(cps.at_or_below(99).is_none());
+        assert_eq!(cps.at_or_below(100).unwrap().height, 100);
+        assert_eq!(cps.at_or_below(199).unwrap().height, 100);
+        assert_eq!(cps.at_or_below(200).unwrap().height, 200);
+        assert_eq!(cps.at_or_below(u32::MAX).unwrap().height, 200);
+    }
+}
@@ -0,0 +1,294 @@
+//! Client side of `kyotod status`, `stop` and `restart`: talk to the running
+//! daemon over its socket, falling back to the pid file and datadir lock
+//! when the socket does not answer.
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use bdk_kyoto::bip157::tokio;
+use tokio::net::UnixStream;
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use crate::lock::{read_pid, DatadirLock, LockError};
+use crate::paths::Layout;
+use crate::server_capnp::server;
+
+const POLL: Duration = Duration::from_millis(200);
+
+pub async fn connect(sock: &Path) -> io::Result<server::Client> {
+    let stream = UnixStream::connect(sock).await?;
+    let (r, w) = stream.into_split();
+    Ok(bootstrap(r, w))
+}
+
+/// Start a capnp client over any byte stream and return the bootstrap
+/// capability. Must be called inside a `LocalSet`.
+pub fn bootstrap<R, W>(r: R, w: W) -> server::Client
+where
+    R: tokio::io::AsyncRead + Unpin + 'static,
+    W: tokio::io::AsyncWrite + Unpin + 'static,
+{
+    let r = futures::io::BufReader::new(r.compat());
+    let w = futures::io::BufWriter::new(w.compat_write());
+    let net = capnp_rpc::twoparty::VatNetwork::new(
+        r,
+        w,
+        capnp_rpc::rpc_twoparty_capnp::Side::Client,
+        Default::default(),
+    );
+    let mut rpc = capnp_rpc::RpcSystem::new(Box::new(net), None);
+    let client: server::Client = rpc.bootstrap(capnp_rpc::rpc_twoparty_capnp::Side::Server);
+    tokio::task::spawn_local(rpc);
+    client
+}
+
+/// The command line a daemon was started with, saved to
+/// `<datadir>/kyotod.cmdline` so `restart` can reproduce it. `KYOTOD_*`
+/// variables are kept too, since they are part of the configuration.
+pub struct Cmdline {
+    cwd: PathBuf,
+    exe: PathBuf,
+    args: Vec<OsString>,
+    env: Vec<(OsString, OsString)>,
+}
+
+impl Cmdline {
+    pub fn current() -> io::Result<Self> {
+        Ok(Self {
+            cwd: std::env::current_dir()?,
+            exe: std::env::current_exe()?,
+            args: std::env::args_os().skip(1).collect(),
+            env: std::env::vars_os()
+                .filter(|(k, _)| k.as_bytes().starts_with(b"KYOTOD_"))
+                .collect(),
+        })
+    }
+
+    // NUL-separated: cwd, exe, arg count, args, then KEY=VALUE pairs.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut fields: Vec<Vec<u8>> = vec![
+            self.cwd.as_os_str().as_bytes().to_vec(),
+            self.exe.as_os_str().as_bytes().to_vec(),
+            self.args.len().to_string().into_bytes(),
+        ];
+        fields.extend(self.args.iter().map(|a| a.as_bytes().to_vec()));
+        for (k, v) in &self.env {
+            let mut kv = k.as_bytes().to_vec();
+            kv.push(b'=');
+            kv.extend_from_slice(v.as_bytes());
+            fields.push(kv);
+        }
+        fs::write(path, fields.join(&0u8))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let bad = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: malformed", path.display()),
+            )
+        };
+        let mut fields = bytes
+            .split(|b| *b == 0)
+            .map(|f| OsString::from_vec(f.to_vec()));
+        let cwd = PathBuf::from(fields.next().ok_or_else(bad)?);
+        let exe = PathBuf::from(fields.next().ok_or_else(bad)?);
+        let count: usize = fields
+            .next()
+            .and_then(|n| n.to_str()?.parse().ok())
+            .ok_or_else(bad)?;
+        let args: Vec<OsString> = fields.by_ref().take(count).collect();
+        if args.len() != count {
+            return Err(bad());
+        }
+        let env = fields
+            .filter(|f| !f.is_empty())
+            .map(|f| {
+                let b = f.as_bytes();
+                let eq = b.iter().position(|c| *c == b'=').ok_or_else(bad)?;
+                Ok((
+                    OsStr::from_bytes(&b[..eq]).to_owned(),
+                    OsStr::from_bytes(&b[eq + 1..]).to_owned(),
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            cwd,
+            exe,
+            args,
+            env,
+        })
+    }
+
+    fn spawn(&self) -> io::Result<()> {
+        Command::new(&self.exe)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .current_dir(&self.cwd)
+            .stdin(Stdio::null())
+            .spawn()
+            .map(|_| ())
+    }
+}
+
+pub struct Status {
+    pub pid: u32,
+    pub uptime: Duration,
+    pub network: String,
+    pub height: Option<u32>,
+    pub progress: Option<f32>,
+    pub peers: u32,
+    pub required_peers: u8,
+    pub wallets: u32,
+    pub degraded: Option<String>,
+    pub restarts: u32,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.uptime.as_secs();
+        writeln!(f, "kyotod is running")?;
+        writeln!(f, "  pid      {}", self.pid)?;
+        writeln!(
+            f,
+            "  uptime   {}h {:02}m {:02}s",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        writeln!(f, "  network  {}", self.network)?;
+        match self.height {
+            Some(h) => writeln!(f, "  height   {h}")?,
+            None => writeln!(f, "  height   -")?,
+        }
+        match self.progress {
+            Some(p) => writeln!(f, "  sync     {p:.1}%")?,
+            None => writeln!(f, "  sync     -")?,
+        }
+        writeln!(f, "  peers    {}/{}", self.peers, self.required_peers)?;
+        match &self.degraded {
+            Some(reason) => write!(f, "  health   degraded: {reason}")?,
+            None => write!(f, "  health   ok")?,
+        }
+        if self.restarts > 0 {
+            write!(f, " ({} restart(s))", self.restarts)?;
+        }
+        writeln!(f)?;
+        write!(f, "  wallets  {}", self.wallets)
+    }
+}
+
+pub async fn status(client: &server::Client) -> Result<Status, capnp::Error> {
+    let resp = client.status_request().send().promise.await?;
+    let s = resp.get()?.get_status()?;
+    Ok(Status {
+        pid: s.get_pid(),
+        uptime: Duration::from_secs(s.get_uptime_secs()),
+        network: s.get_network()?.to_string()?,
+        height: s.get_has_height().then(|| s.get_height()),
+        progress: s.get_has_progress().then(|| s.get_percent()),
+        peers: s.get_peers(),
+        required_peers: s.get_required_peers(),
+        wallets: s.get_wallets(),
+        degraded: Some(s.get_degraded()?.to_string()?).filter(|d| !d.is_empty()),
+        restarts: s.get_restarts(),
+    })
+}
+
+/// Whether a daemon holds the datadir lock right now.
+pub fn is_running(layout: &Layout) -> io::Result<bool> {
+    match DatadirLock::acquire(layout) {
+        Ok(_) => Ok(false),
+        Err(LockError::Held { .. }) => Ok(true),
+        Err(LockError::Io(e)) => Err(e),
+    }
+}
+
+/// How `stop` asked the daemon to exit.
+pub enum Stopped {
+    Shutdown,
+    /// SIGTERM, with the error from `shutdown` when the socket answered but
+    /// the request failed.
+    Signalled(Option<String>),
+}
+
+/// Ask the daemon to shut down and wait for it to release the datadir lock.
+/// Uses `shutdown` with the admin cookie when the socket answers, SIGTERM to
+/// the pid in `node.pid` otherwise.
+pub async fn stop(layout: &Layout, timeout: Duration) -> Result<Stopped, String> {
+    if !is_running(layout).map_err(|e| e.to_string())? {
+        return Err("kyotod is not running".into());
+    }
+    let how = match connect(&layout.socket()).await {
+        Ok(client) => match shutdown(client, layout).await {
+            Ok(()) => Stopped::Shutdown,
+            Err(e) => Stopped::Signalled(Some(e)),
+        },
+        Err(_) => Stopped::Signalled(None),
+    };
+    if let Stopped::Signalled(_) = how {
+        let pid =
+            read_pid(&layout.pid_file()).ok_or("socket is unreachable and there is no pid file")?;
+        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } == -1 {
+            return Err(format!("kill {pid}: {}", io::Error::last_os_error()));
+        }
+    }
+    let deadline = Instant::now() + timeout;
+    while is_running(layout).map_err(|e| e.to_string())? {
+        if Instant::now() >= deadline {
+            return Err(format!("still running after {}s", timeout.as_secs()));
+        }
+        tokio::time::sleep(POLL).await;
+    }
+    Ok(how)
+}
+
+/// Stop the daemon if it is running, then start it again with the command
+/// line it was originally given, and wait for its socket to answer.
+pub async fn restart(layout: &Layout, timeout: Duration) -> Result<Status, String> {
+    let cmdline =
+        Cmdline::load(&layout.cmdline_file()).map_err(|e| format!("original command line: {e}"))?;
+    if is_running(layout).map_err(|e| e.to_string())? {
+        stop(layout, timeout).await?;
+    }
+    cmdline
+        .spawn()
+        .map_err(|e| format!("start {}: {e}", cmdline.exe.display()))?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Ok(client) = connect(&layout.socket()).await {
+            if let Ok(s) = status(&client).await {
+                return Ok(s);
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("not answering after {}s", timeout.as_secs()));
+        }
+        tokio::time::sleep(POLL).await;
+    }
+}
+
+async fn shutdown(client: server::Client, layout: &Layout) -> Result<(), String> {
+    let token = fs::read_to_string(layout.admin_cookie())
+        .map_err(|e| format!("{}: {e}", layout.admin_cookie().display()))?;
+    let mut req = client.authenticate_request();
+    req.get().set_token(token.trim());
+    let resp = req.send().promise.await.map_err(|e| e.to_string())?;
+    let admin = resp
+        .get()
+        .and_then(|r| r.get_server())
+        .map_err(|e| e.to_string())?;
+    admin
+        .shutdown_request()
+        .send()
+        .promise
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use std::{
-    fs::{self, File},
+    fs::{self, File, OpenOptions},
     os::fd::IntoRawFd,
+    path::PathBuf,
 };
 
 const MASK_OCTAL: libc::mode_t = 0o027;
@@ -8,11 +9,22 @@ const DEV_NULL: &str = "/dev/null";
 
 pub struct Daemonize {
     working_directory: String,
+    log_file: PathBuf,
 }
 
 impl Daemonize {
     pub fn new(working_directory: String) -> Self {
-        Self { working_directory }
+        let log_file = PathBuf::from(format!("{working_directory}/node.log"));
+        Self {
+            working_directory,
+            log_file,
+        }
+    }
+
+    /// Where stdout and stderr go once detached. Appended to, never truncated.
+    pub fn log_file(mut self, path: PathBuf) -> Self {
+        self.log_file = path;
+        self
     }
 
     pub fn fork(self) -> std::io::Result<()> {
@@ -31,8 +43,10 @@ impl Daemonize {
         let pid_file = format!("{}/node.pid", self.working_directory);
         let process_id = std::process::id();
         fs::write(pid_file, process_id.to_string())?;
-        let log_file = format!("{}/node.log", self.working_directory);
-        let log_file = File::create(log_file)?;
+        let log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_file)?;
         unsafe { libc::dup2(log_file.into_raw_fd(), libc::STDOUT_FILENO) };
         unsafe { libc::dup2(libc::STDOUT_FILENO, libc::STDERR_FILENO) };
         Ok(())
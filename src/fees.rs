@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_kyoto::Requester;
+use bdk_wallet::bitcoin::{Block, BlockHash, FeeRate, Network};
+use tracing::{debug, info, warn};
+
+pub type FeeCache = Arc<Mutex<BlockFees>>;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const INITIAL_SUBSIDY_SATS: u64 = 50 * 100_000_000;
+
+/// Average fee rates of the most recent `depth` blocks, keyed by height.
+///
+/// A light client never sees the prevouts of the transactions it downloads,
+/// so per-transaction fee rates are out of reach. Instead each block is
+/// summarized by its coinbase reward minus the subsidy, divided by the
+/// virtual size of its non-coinbase transactions; the estimates are
+/// percentiles over those per-block rates.
+pub struct BlockFees {
+    depth: u32,
+    blocks: BTreeMap<u32, (BlockHash, FeeRate)>,
+}
+
+#[derive(Clone, Copy)]
+pub struct FeeEstimates {
+    pub low: FeeRate,
+    pub medium: FeeRate,
+    pub high: FeeRate,
+    pub blocks: u32,
+}
+
+impl BlockFees {
+    pub fn new(depth: u32) -> Self {
+        Self {
+            depth: depth.max(1),
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_depth(&mut self, depth: u32) {
+        self.depth = depth.max(1);
+    }
+
+    pub fn estimates(&self) -> Option<FeeEstimates> {
+        let mut rates: Vec<u64> = self
+            .blocks
+            .values()
+            .map(|(_, r)| r.to_sat_per_kwu())
+            .collect();
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_unstable();
+        let pct = |p: usize| FeeRate::from_sat_per_kwu(rates[(rates.len() - 1) * p / 100]);
+        Some(FeeEstimates {
+            low: pct(10),
+            medium: pct(50),
+            high: pct(90),
+            blocks: rates.len() as u32,
+        })
+    }
+
+    /// Pick a preset by confirmation target: next block gets `high`, up to
+    /// three blocks `medium`, anything slower `low`.
+    pub fn for_target(&self, target_blocks: u16) -> Option<FeeRate> {
+        let e = self.estimates()?;
+        Some(match target_blocks {
+            0 | 1 => e.high,
+            2 | 3 => e.medium,
+            _ => e.low,
+        })
+    }
+}
+
+impl FeeEstimates {
+    /// A human-readable warning when `chosen` is far outside the observed
+    /// range: under half of `low`, or over twice `high`.
+    pub fn check(&self, chosen: FeeRate) -> Option<String> {
+        let c = chosen.to_sat_per_kwu();
+        if c * 2 < self.low.to_sat_per_kwu() {
+            Some(format!(
+                "{:.1} sat/vB is well below recent blocks (low {:.1}); may not confirm",
+                sat_per_vb(chosen),
+                sat_per_vb(self.low),
+            ))
+        } else if c > self.high.to_sat_per_kwu() * 2 {
+            Some(format!(
+                "{:.1} sat/vB is well above recent blocks (high {:.1}); overpaying",
+                sat_per_vb(chosen),
+                sat_per_vb(self.high),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+pub fn sat_per_vb(rate: FeeRate) -> f64 {
+    rate.to_sat_per_kwu() as f64 / 250.0
+}
+
+/// Keep `cache` filled with the fee rates of the last `depth` blocks below the
+/// chain tip. Runs for the lifetime of the light client it was spawned with.
+pub async fn refresh_loop(network: Network, requester: Requester, cache: FeeCache) {
+    let mut tick = tokio::time::interval(REFRESH_INTERVAL);
+    let mut last_tip: Option<BlockHash> = None;
+    loop {
+        tick.tick().await;
+        let tip = match requester.chain_tip().await {
+            Ok(t) => t,
+            Err(e) => {
+                debug!(target: "node", "fee estimator: chain tip: {e}");
+                continue;
+            }
+        };
+        if last_tip == Some(tip.hash) {
+            continue;
+        }
+        let depth = cache.lock().unwrap().depth;
+        let start = tip.height.saturating_sub(depth - 1);
+        for height in start..=tip.height {
+            let hash = match requester.get_header(height).await {
+                Ok(Some(ih)) => ih.header.block_hash(),
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(target: "node", "fee estimator: get_header({height}): {e}");
+                    continue;
+                }
+            };
+            let known = cache
+                .lock()
+                .unwrap()
+                .blocks
+                .get(&height)
+                .is_some_and(|(h, _)| *h == hash);
+            if known {
+                continue;
+            }
+            let block = match requester.get_block(hash).await {
+                Ok(ib) => ib.block,
+                Err(e) => {
+                    warn!(target: "node", "fee estimator: get_block({hash}): {e}");
+                    continue;
+                }
+            };
+            if let Some(rate) = block_fee_rate(network, height, &block) {
+                cache.lock().unwrap().blocks.insert(height, (hash, rate));
+            }
+        }
+        {
+            let mut c = cache.lock().unwrap();
+            c.blocks.retain(|h, _| *h >= start && *h <= tip.height);
+            if let Some(e) = c.estimates() {
+                info!(
+                    target: "node",
+                    "fee estimates over {} block(s): low {:.1} medium {:.1} high {:.1} sat/vB",
+                    e.blocks,
+                    sat_per_vb(e.low),
+                    sat_per_vb(e.medium),
+                    sat_per_vb(e.high),
+                );
+            }
+        }
+        last_tip = Some(tip.hash);
+    }
+}
+
+fn block_fee_rate(network: Network, height: u32, block: &Block) -> Option<FeeRate> {
+    let coinbase = block.txdata.first()?;
+    let reward: u64 = coinbase.output.iter().map(|o| o.value.to_sat()).sum();
+    let fees = reward.checked_sub(subsidy(network, height))?;
+    let vbytes = (block.weight() - coinbase.weight()).to_vbytes_ceil();
+    if vbytes == 0 {
+        return None;
+    }
+    Some(FeeRate::from_sat_per_kwu(fees * 250 / vbytes))
+}
+
+fn subsidy(network: Network, height: u32) -> u64 {
+    let interval = match network {
+        Network::Regtest => 150,
+        _ => 210_000,
+    };
+    let halvings = height / interval;
+    if halvings >= 64 {
+        0
+    } else {
+        INITIAL_SUBSIDY_SATS >> halvings
+    }
+}
@@ -0,0 +1,206 @@
+//! `walletnotify` and `blocknotify`: run a user command when a wallet sees a
+//! new or newly confirmed transaction, or when the chain tip advances.
+
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::{BlockHash, Txid};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const POLL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub walletnotify: Option<String>,
+    pub blocknotify: Option<String>,
+    /// Runs allowed per minute across both commands; the rest are dropped.
+    pub per_minute: u32,
+    pub timeout: Duration,
+}
+
+enum Event {
+    Wallet {
+        wallet: String,
+        txid: Txid,
+        height: Option<u32>,
+    },
+    Block {
+        height: u32,
+        hash: BlockHash,
+    },
+}
+
+/// Cheap to clone; all clones feed the same runner task.
+#[derive(Clone)]
+pub struct Hooks {
+    tx: mpsc::UnboundedSender<Event>,
+    settings: Arc<Mutex<Settings>>,
+    // Several wallets reach the same tip; announce each height once.
+    last_block: Arc<AtomicU32>,
+}
+
+impl Hooks {
+    /// Start the runner task. Must be called inside a tokio runtime.
+    pub fn spawn(settings: Settings) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let settings = Arc::new(Mutex::new(settings));
+        tokio::spawn(run(rx, settings.clone()));
+        Self {
+            tx,
+            settings,
+            last_block: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Another handle on the same runner and settings for a second network,
+    /// which announces block heights on its own.
+    pub fn for_network(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            settings: self.settings.clone(),
+            last_block: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Replace the commands and limits; runs already started keep theirs.
+    pub fn set(&self, settings: Settings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    /// `height` is the confirmation height, if the transaction is confirmed.
+    pub fn wallet_tx(&self, wallet: &str, txid: Txid, height: Option<u32>) {
+        if self.settings.lock().unwrap().walletnotify.is_some() {
+            let _ = self.tx.send(Event::Wallet {
+                wallet: wallet.to_string(),
+                txid,
+                height,
+            });
+        }
+    }
+
+    pub fn block(&self, height: u32, hash: BlockHash) {
+        if self.last_block.fetch_max(height, Ordering::Relaxed) >= height {
+            return;
+        }
+        if self.settings.lock().unwrap().blocknotify.is_some() {
+            let _ = self.tx.send(Event::Block { height, hash });
+        }
+    }
+}
+
+async fn run(mut rx: mpsc::UnboundedReceiver<Event>, settings: Arc<Mutex<Settings>>) {
+    let mut window = Instant::now();
+    let mut used = 0u32;
+    let mut dropped = 0u64;
+    while let Some(event) = rx.recv().await {
+        let s = settings.lock().unwrap().clone();
+        let (name, command) = match &event {
+            Event::Wallet {
+                wallet,
+                txid,
+                height,
+            } => (
+                "walletnotify",
+                s.walletnotify
+                    .as_deref()
+                    .map(|c| substitute(c, Some(wallet), &txid.to_string(), *height)),
+            ),
+            Event::Block { height, hash } => (
+                "blocknotify",
+                s.blocknotify
+                    .as_deref()
+                    .map(|c| substitute(c, None, &hash.to_string(), Some(*height))),
+            ),
+        };
+        let Some(command) = command else { continue };
+        if window.elapsed() >= RATE_WINDOW {
+            if dropped > 0 {
+                warn!(
+                    target: "node",
+                    "notify: dropped {dropped} event(s) over the limit of {}/min",
+                    s.per_minute
+                );
+            }
+            window = Instant::now();
+            used = 0;
+            dropped = 0;
+        }
+        if used >= s.per_minute {
+            dropped += 1;
+            continue;
+        }
+        used += 1;
+        tokio::spawn(execute(name, command, s.timeout));
+    }
+}
+
+// `%w` is shell-quoted since wallet names come from file names; txids, block
+// hashes and heights are plain hex and digits. `%h` is empty for an
+// unconfirmed transaction and `%w` for a block.
+fn substitute(template: &str, wallet: Option<&str>, id: &str, height: Option<u32>) -> String {
+    let wallet = wallet
+        .map(|w| format!("'{}'", w.replace('\'', r"'\''")))
+        .unwrap_or_default();
+    template
+        .replace("%s", id)
+        .replace("%h", &height.map(|h| h.to_string()).unwrap_or_default())
+        .replace("%w", &wallet)
+}
+
+async fn execute(name: &'static str, command: String, timeout: Duration) {
+    debug!(target: "node", "{name}: running `{command}`");
+    let started = Instant::now();
+    let mut child = match Command::new("/bin/sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(target: "node", "{name}: spawn `{command}`: {e}");
+            return;
+        }
+    };
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => tokio::time::sleep(POLL).await,
+            Err(e) => {
+                warn!(target: "node", "{name}: wait `{command}`: {e}");
+                return;
+            }
+        }
+    };
+    let ms = started.elapsed().as_millis();
+    match status {
+        Some(s) if s.success() => info!(target: "node", "{name}: `{command}` exited 0 ({ms} ms)"),
+        Some(s) => warn!(target: "node", "{name}: `{command}` {} ({ms} ms)", describe(s)),
+        None => warn!(
+            target: "node",
+            "{name}: `{command}` killed after {}s timeout",
+            timeout.as_secs()
+        ),
+    }
+}
+
+fn describe(status: ExitStatus) -> String {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exited {code}"),
+        (None, Some(sig)) => format!("killed by signal {sig}"),
+        _ => status.to_string(),
+    }
+}
@@ -1,76 +1,169 @@
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::net::SocketAddr;
+use std::os::fd::OwnedFd;
 use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use std::str::FromStr;
 
 use bdk_kyoto::bip157::tokio;
 use bdk_kyoto::Requester;
 use bdk_wallet::bitcoin::consensus::{self, Decodable};
-use bdk_wallet::bitcoin::{Address, Amount, FeeRate, Psbt, Transaction};
+use bdk_wallet::bitcoin::{Address, Amount, FeeRate, Network, Psbt, Transaction};
 use bdk_wallet::chain::ChainPosition;
 use bdk_wallet::{KeychainKind, SignOptions};
 use bip139::WalletBackup;
-use tokio::net::UnixListener;
-use tokio::sync::mpsc;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::{mpsc, oneshot};
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
+use crate::access::SocketAccess;
+use crate::auth::{Cookies, Tier};
+use crate::bip21;
+use crate::chain::{Chain, Chains};
+use crate::fees;
+use crate::logbuf::{LogBuffer, LogFilter, Slice};
+use crate::logging::LogControl;
+use crate::metrics::{SharedMetrics, Source};
 use crate::paths::Layout;
+use crate::remote;
 use crate::server_capnp;
-use crate::sync::{ProgressSlot, RequiredPeers, TorProxy, TrustedPeers};
-use crate::wallet::{self, State};
+use crate::sync::{self, RequiredPeers, TorProxy};
+use crate::wallet;
 
 pub type RequesterSlot = Arc<Mutex<Option<Requester>>>;
 
+/// What a config reload changed, and what it refused to change.
+#[derive(Default)]
+pub struct ReloadReport {
+    pub applied: Vec<String>,
+    pub rejected: Vec<String>,
+}
+
+/// Asks the main loop to reload the config and answer on the enclosed channel.
+pub type ReloadRequest = oneshot::Sender<ReloadReport>;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Most log lines sent to a tail in one `push`.
+const LOG_BATCH: usize = 256;
+
+#[derive(Clone)]
 pub struct IpcInterface {
+    tier: Tier,
+    /// A paired TCP client. Its tier was fixed at pairing, so it may not
+    /// `authenticate` its way past it with a cookie.
+    remote: bool,
+    cookies: Arc<Cookies>,
     shutdown_tx: mpsc::Sender<()>,
-    rebuild_tx: mpsc::Sender<()>,
-    state: Arc<Mutex<State>>,
-    requester: RequesterSlot,
-    progress: ProgressSlot,
+    rebuild_tx: mpsc::Sender<Network>,
+    reload_tx: mpsc::Sender<ReloadRequest>,
+    /// The network this connection is bound to; see `useNetwork`.
+    chain: Chain,
+    chains: Chains,
     required_peers: RequiredPeers,
-    trusted_peers: TrustedPeers,
     tor_proxy: TorProxy,
-    layout: Arc<Layout>,
-    network: bdk_wallet::bitcoin::Network,
+    log: LogControl,
+    logs: LogBuffer,
+    metrics: SharedMetrics,
+    started: Instant,
 }
 
 impl IpcInterface {
     pub fn new(
+        cookies: Arc<Cookies>,
         shutdown_tx: mpsc::Sender<()>,
-        rebuild_tx: mpsc::Sender<()>,
-        state: Arc<Mutex<State>>,
-        requester: RequesterSlot,
-        progress: ProgressSlot,
+        rebuild_tx: mpsc::Sender<Network>,
+        reload_tx: mpsc::Sender<ReloadRequest>,
+        chain: Chain,
+        chains: Chains,
         required_peers: RequiredPeers,
-        trusted_peers: TrustedPeers,
         tor_proxy: TorProxy,
-        layout: Arc<Layout>,
-        network: bdk_wallet::bitcoin::Network,
+        log: LogControl,
+        logs: LogBuffer,
+        metrics: SharedMetrics,
+        started: Instant,
     ) -> Self {
         Self {
+            tier: Tier::ReadOnly,
+            remote: false,
+            cookies,
             shutdown_tx,
             rebuild_tx,
-            state,
-            requester,
-            progress,
+            reload_tx,
+            chain,
+            chains,
             required_peers,
-            trusted_peers,
             tor_proxy,
-            layout,
-            network,
+            log,
+            logs,
+            metrics,
+            started,
+        }
+    }
+
+    fn require(&self, tier: Tier) -> Result<(), capnp::Error> {
+        if self.tier >= tier {
+            Ok(())
+        } else {
+            Err(failed(format!(
+                "{tier} access required; this connection is {}",
+                self.tier
+            )))
         }
     }
 
     fn requester(&self) -> Result<Requester, capnp::Error> {
-        self.requester
+        self.chain
+            .requester
             .lock()
             .unwrap()
             .clone()
             .ok_or_else(|| failed("no wallets loaded; node is not running"))
     }
+
+    /// Send `tx` under the broadcast policy and track it for rebroadcast.
+    /// With a delay the send happens in the background and this returns at
+    /// once. Returns the txid and where it went or will go.
+    async fn broadcast(&self, tx: Transaction) -> Result<(String, String), capnp::Error> {
+        let txid = tx.compute_txid().to_string();
+        let broadcaster = self.chain.broadcaster.clone();
+        let delay = broadcaster.delay();
+        if delay.is_zero() {
+            let via = broadcaster
+                .send(&tx, Source::Ipc, delay)
+                .await
+                .map_err(|e| failed(format!("broadcast: {e}")))?;
+            self.chain.pending.lock().unwrap().track(tx);
+            return Ok((txid, format!("sent via {via}")));
+        }
+        // Tracked first, so a failed send or a restart is covered by rebroadcast.
+        self.chain.pending.lock().unwrap().track(tx.clone());
+        let route = format!(
+            "sending via {} in {}s",
+            broadcaster.policy().route.describe(),
+            delay.as_secs()
+        );
+        tokio::task::spawn_local(async move {
+            tokio::time::sleep(delay).await;
+            let _ = broadcaster.send(&tx, Source::Ipc, delay).await;
+        });
+        Ok((txid, route))
+    }
+
+    /// Ask for a rebuild of every network whose light client is running, and
+    /// return how many were asked.
+    async fn rebuild_running(&self) -> Result<usize, mpsc::error::SendError<Network>> {
+        let mut n = 0;
+        for chain in self.chains.iter().filter(|c| c.running()) {
+            self.rebuild_tx.send(chain.network).await?;
+            n += 1;
+        }
+        Ok(n)
+    }
 }
 
 fn valid_wallet_name(name: &str) -> bool {
@@ -85,41 +178,84 @@ fn failed(msg: impl Into<String>) -> capnp::Error {
 }
 
 pub struct ServerArgs {
+    /// The datadir itself, for the socket and remote keys.
     pub layout: Arc<Layout>,
-    pub network: bdk_wallet::bitcoin::Network,
     pub shutdown_tx: mpsc::Sender<()>,
-    pub rebuild_tx: mpsc::Sender<()>,
-    pub state: Arc<Mutex<State>>,
-    pub requester: RequesterSlot,
-    pub progress: ProgressSlot,
+    pub rebuild_tx: mpsc::Sender<Network>,
+    pub reload_tx: mpsc::Sender<ReloadRequest>,
+    /// Connections start on the first one.
+    pub chains: Chains,
     pub required_peers: RequiredPeers,
-    pub trusted_peers: TrustedPeers,
     pub tor_proxy: TorProxy,
+    pub log: LogControl,
+    pub logs: LogBuffer,
+    pub metrics: SharedMetrics,
+    pub access: SocketAccess,
+    pub cookies: Arc<Cookies>,
+    pub remote_listen: Option<SocketAddr>,
+    pub started: Instant,
+    /// A listening socket inherited from the service manager. When set, the
+    /// daemon neither binds nor re-permissions `layout.socket()`.
+    pub listen_fd: Option<OwnedFd>,
 }
 
-pub fn spawn_server(args: ServerArgs) {
+/// Start the IPC thread. The returned receiver fires once the local socket
+/// is listening, and is dropped unanswered if binding fails.
+pub fn spawn_server(mut args: ServerArgs) -> oneshot::Receiver<()> {
+    let (bound_tx, bound_rx) = oneshot::channel();
+    let listen_fd = args.listen_fd.take();
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .expect("ipc runtime");
         rt.block_on(async move {
-            tokio::task::LocalSet::new().run_until(accept_loop(args)).await;
+            let args = Rc::new(args);
+            let local = tokio::task::LocalSet::new();
+            if let Some(addr) = args.remote_listen {
+                local.spawn_local(remote_loop(args.clone(), addr));
+            }
+            local
+                .run_until(accept_loop(args, listen_fd, bound_tx))
+                .await;
         });
     });
+    bound_rx
 }
 
-async fn accept_loop(args: ServerArgs) {
+fn bind(args: &ServerArgs, listen_fd: Option<OwnedFd>) -> Result<UnixListener, String> {
     let socket_path = args.layout.socket();
-    let _ = std::fs::remove_file(&socket_path);
-    let listener = match UnixListener::bind(&socket_path) {
+    if let Some(fd) = listen_fd {
+        let std_listener = std::os::unix::net::UnixListener::from(fd);
+        std_listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("inherited socket: {e}"))?;
+        debug!(target: "ipc", "using socket passed by the service manager");
+        return UnixListener::from_std(std_listener).map_err(|e| format!("inherited socket: {e}"));
+    }
+    crate::lock::clear_stale_socket(&socket_path).map_err(|e| e.to_string())?;
+    let listener = args
+        .access
+        .bind(&socket_path)
+        .and_then(|l| l.set_nonblocking(true).map(|()| l))
+        .map_err(|e| format!("bind {}: {e}", socket_path.display()))?;
+    debug!(target: "ipc", "listening on {}", socket_path.display());
+    UnixListener::from_std(listener).map_err(|e| format!("bind {}: {e}", socket_path.display()))
+}
+
+async fn accept_loop(
+    args: Rc<ServerArgs>,
+    listen_fd: Option<OwnedFd>,
+    bound_tx: oneshot::Sender<()>,
+) {
+    let listener = match bind(&args, listen_fd) {
         Ok(l) => l,
         Err(e) => {
-            error!(target: "ipc", "bind {}: {e}", socket_path.display());
+            error!(target: "ipc", "{e}");
             return;
         }
     };
-    debug!(target: "ipc", "listening on {}", socket_path.display());
+    let _ = bound_tx.send(());
     loop {
         let stream = match listener.accept().await {
             Ok((s, _)) => s,
@@ -128,39 +264,124 @@ async fn accept_loop(args: ServerArgs) {
                 continue;
             }
         };
+        match stream.peer_cred() {
+            Ok(cred) if args.access.permits(&cred) => {}
+            Ok(cred) => {
+                warn!(
+                    target: "ipc",
+                    "rejected connection from uid {} (gid {}, pid {:?})",
+                    cred.uid(),
+                    cred.gid(),
+                    cred.pid()
+                );
+                continue;
+            }
+            Err(e) => {
+                warn!(target: "ipc", "rejected connection: peer credentials: {e}");
+                continue;
+            }
+        }
         let (reader, writer) = stream.into_split();
-        let reader = futures::io::BufReader::new(reader.compat());
-        let writer = futures::io::BufWriter::new(writer.compat_write());
-        let net = capnp_rpc::twoparty::VatNetwork::new(
-            reader,
-            writer,
-            capnp_rpc::rpc_twoparty_capnp::Side::Server,
-            Default::default(),
-        );
-        let interface = IpcInterface::new(
-            args.shutdown_tx.clone(),
-            args.rebuild_tx.clone(),
-            args.state.clone(),
-            args.requester.clone(),
-            args.progress.clone(),
-            args.required_peers.clone(),
-            args.trusted_peers.clone(),
-            args.tor_proxy.clone(),
-            args.layout.clone(),
-            args.network,
-        );
-        let client: server_capnp::server::Client = capnp_rpc::new_client(interface);
-        let rpc = capnp_rpc::RpcSystem::new(Box::new(net), Some(client.client));
-        tokio::task::spawn_local(rpc);
+        serve(&args, reader, writer, Tier::ReadOnly, false);
     }
 }
 
+// Remote clients skip the cookie dance: the tier was fixed when their key
+// was paired, and the handshake proves they hold that key.
+async fn remote_loop(args: Rc<ServerArgs>, addr: SocketAddr) {
+    let server_key = match remote::Keypair::load_or_create(&args.layout.remote_key()) {
+        Ok(k) => Rc::new(k),
+        Err(e) => {
+            error!(target: "ipc", "remote key: {e}");
+            return;
+        }
+    };
+    let listener = match TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!(target: "ipc", "bind {addr}: {e}");
+            return;
+        }
+    };
+    debug!(
+        target: "ipc",
+        "listening on {addr} (server key {})",
+        server_key.public
+    );
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(s) => s,
+            Err(e) => {
+                error!(target: "ipc", "accept: {e}");
+                continue;
+            }
+        };
+        let args = args.clone();
+        let server_key = server_key.clone();
+        tokio::task::spawn_local(async move {
+            let clients = match remote::authorized_clients(&args.layout.remote_clients()) {
+                Ok(c) => c,
+                Err(e) => {
+                    error!(target: "ipc", "paired clients: {e}");
+                    return;
+                }
+            };
+            let handshake = remote::accept(stream, &server_key, &clients);
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+                Ok(Ok((plain, key, tier))) => {
+                    debug!(target: "ipc", "remote {peer}: client {key} ({tier})");
+                    let (reader, writer) = tokio::io::split(plain);
+                    serve(&args, reader, writer, tier, true);
+                }
+                Ok(Err(e)) => warn!(target: "ipc", "rejected remote {peer}: {e}"),
+                Err(_) => warn!(target: "ipc", "rejected remote {peer}: handshake timed out"),
+            }
+        });
+    }
+}
+
+fn serve<R, W>(args: &ServerArgs, reader: R, writer: W, tier: Tier, remote: bool)
+where
+    R: tokio::io::AsyncRead + Unpin + 'static,
+    W: tokio::io::AsyncWrite + Unpin + 'static,
+{
+    let reader = futures::io::BufReader::new(reader.compat());
+    let writer = futures::io::BufWriter::new(writer.compat_write());
+    let net = capnp_rpc::twoparty::VatNetwork::new(
+        reader,
+        writer,
+        capnp_rpc::rpc_twoparty_capnp::Side::Server,
+        Default::default(),
+    );
+    let mut interface = IpcInterface::new(
+        args.cookies.clone(),
+        args.shutdown_tx.clone(),
+        args.rebuild_tx.clone(),
+        args.reload_tx.clone(),
+        args.chains[0].clone(),
+        args.chains.clone(),
+        args.required_peers.clone(),
+        args.tor_proxy.clone(),
+        args.log.clone(),
+        args.logs.clone(),
+        args.metrics.clone(),
+        args.started,
+    );
+    interface.tier = tier;
+    interface.remote = remote;
+    let client: server_capnp::server::Client = capnp_rpc::new_client(interface);
+    let rpc = capnp_rpc::RpcSystem::new(Box::new(net), Some(client.client));
+    tokio::task::spawn_local(rpc);
+}
+
 impl server_capnp::server::Server for IpcInterface {
     async fn shutdown(
         self: capnp::capability::Rc<Self>,
         _: server_capnp::server::ShutdownParams,
         _: server_capnp::server::ShutdownResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("shutdown");
+        self.require(Tier::Admin)?;
         let _ = self.shutdown_tx.send(()).await;
         Ok(())
     }
@@ -170,8 +391,10 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::SetActiveParams,
         mut results: server_capnp::server::SetActiveResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("setActive");
+        self.require(Tier::Spender)?;
         let name = params.get()?.get_name()?.to_string()?;
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.chain.state.lock().unwrap();
         match state.set_active(&name) {
             Ok(()) => {
                 let mut r = results.get();
@@ -192,8 +415,10 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::ExportWalletParams,
         mut results: server_capnp::server::ExportWalletResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("exportWallet");
+        self.require(Tier::Spender)?;
         let name = params.get()?.get_name()?.to_string()?;
-        let state = self.state.lock().unwrap();
+        let state = self.chain.state.lock().unwrap();
         let entry = state
             .wallets
             .get(&name)
@@ -208,10 +433,18 @@ impl server_capnp::server::Server for IpcInterface {
 
     async fn receive(
         self: capnp::capability::Rc<Self>,
-        _: server_capnp::server::ReceiveParams,
+        params: server_capnp::server::ReceiveParams,
         mut results: server_capnp::server::ReceiveResults,
     ) -> Result<(), capnp::Error> {
-        let mut state = self.state.lock().unwrap();
+        let _timer = self.metrics.ipc_call("receive");
+        self.require(Tier::Spender)?;
+        let p = params.get()?;
+        let amount = Some(p.get_amount())
+            .filter(|a| *a > 0)
+            .map(Amount::from_sat);
+        let label = p.get_label()?.to_string()?;
+        let message = p.get_message()?.to_string()?;
+        let mut state = self.chain.state.lock().unwrap();
         let entry = state
             .active_entry_mut()
             .ok_or_else(|| failed("no active wallet"))?;
@@ -220,7 +453,10 @@ impl server_capnp::server::Server for IpcInterface {
             .wallet
             .persist(&mut entry.conn)
             .map_err(|e| failed(format!("persist: {e}")))?;
-        results.get().set_address(info.address.to_string().as_str());
+        let uri = bip21::build(&info.address, amount, Some(&label), Some(&message));
+        let mut r = results.get();
+        r.set_address(info.address.to_string().as_str());
+        r.set_uri(uri.as_str());
         Ok(())
     }
 
@@ -229,13 +465,19 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::BalanceParams,
         mut results: server_capnp::server::BalanceResults,
     ) -> Result<(), capnp::Error> {
-        let state = self.state.lock().unwrap();
+        let _timer = self.metrics.ipc_call("balance");
+        let state = self.chain.state.lock().unwrap();
         let entry = state
             .active_entry()
             .ok_or_else(|| failed("no active wallet"))?;
-        results
-            .get()
-            .set_sats(entry.wallet.balance().total().to_sat());
+        let b = entry.wallet.balance();
+        let mut r = results.get();
+        r.set_sats(b.total().to_sat());
+        r.set_confirmed(b.confirmed.to_sat());
+        r.set_trusted_pending(b.trusted_pending.to_sat());
+        r.set_untrusted_pending(b.untrusted_pending.to_sat());
+        r.set_immature(b.immature.to_sat());
+        r.set_spendable(b.trusted_spendable().to_sat());
         Ok(())
     }
 
@@ -244,7 +486,8 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::BalancesParams,
         mut results: server_capnp::server::BalancesResults,
     ) -> Result<(), capnp::Error> {
-        let state = self.state.lock().unwrap();
+        let _timer = self.metrics.ipc_call("balances");
+        let state = self.chain.state.lock().unwrap();
         let mut names: Vec<&String> = state.wallets.keys().collect();
         names.sort();
         let mut list = results.get().init_entries(names.len() as u32);
@@ -252,8 +495,14 @@ impl server_capnp::server::Server for IpcInterface {
             let entry = state.wallets.get(*name).unwrap();
             let mut row = list.reborrow().get(i as u32);
             row.set_name(name.as_str());
-            row.set_sats(entry.wallet.balance().total().to_sat());
+            let b = entry.wallet.balance();
+            row.set_sats(b.total().to_sat());
             row.set_active(state.active.as_deref() == Some(name.as_str()));
+            row.set_confirmed(b.confirmed.to_sat());
+            row.set_trusted_pending(b.trusted_pending.to_sat());
+            row.set_untrusted_pending(b.untrusted_pending.to_sat());
+            row.set_immature(b.immature.to_sat());
+            row.set_spendable(b.trusted_spendable().to_sat());
         }
         Ok(())
     }
@@ -263,7 +512,8 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::HistoryParams,
         mut results: server_capnp::server::HistoryResults,
     ) -> Result<(), capnp::Error> {
-        let state = self.state.lock().unwrap();
+        let _timer = self.metrics.ipc_call("history");
+        let state = self.chain.state.lock().unwrap();
         let entry = state
             .active_entry()
             .ok_or_else(|| failed("no active wallet"))?;
@@ -281,18 +531,27 @@ impl server_capnp::server::Server for IpcInterface {
                 let txid = t.tx_node.txid.to_string();
                 let short = format!("{}…{}", &txid[..8], &txid[txid.len() - 4..]);
                 let when = match &t.chain_position {
-                    ChainPosition::Confirmed { anchor, .. } => format!("block {}", anchor.block_id.height),
+                    ChainPosition::Confirmed { anchor, .. } => {
+                        format!("block {}", anchor.block_id.height)
+                    }
                     ChainPosition::Unconfirmed { .. } => "unconfirmed".to_string(),
                 };
                 let sort_key = match &t.chain_position {
                     ChainPosition::Confirmed { anchor, .. } => -(anchor.block_id.height as i64),
                     ChainPosition::Unconfirmed { .. } => i64::MIN,
                 };
-                (sort_key, format!("{dir}  {amt:>12} sats   {short}   {when}"))
+                (
+                    sort_key,
+                    format!("{dir}  {amt:>12} sats   {short}   {when}"),
+                )
             })
             .collect();
         rows.sort_by_key(|(k, _)| *k);
-        let text = rows.into_iter().map(|(_, s)| s).collect::<Vec<_>>().join("\n");
+        let text = rows
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect::<Vec<_>>()
+            .join("\n");
         results.get().set_entries(text.as_str());
         Ok(())
     }
@@ -302,15 +561,15 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::BroadcastTxParams,
         mut results: server_capnp::server::BroadcastTxResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("broadcastTx");
+        self.require(Tier::Spender)?;
         let mut raw = params.get()?.get_tx()?;
         let tx = Transaction::consensus_decode(&mut raw)
             .map_err(|e| failed(format!("decode tx: {e}")))?;
-        let txid = tx.compute_txid().to_string();
-        self.requester()?
-            .submit_package(tx)
-            .await
-            .map_err(|e| failed(format!("broadcast: {e}")))?;
-        results.get().set_txid(txid.as_str());
+        let (txid, route) = self.broadcast(tx).await?;
+        let mut r = results.get();
+        r.set_txid(txid.as_str());
+        r.set_route(route.as_str());
         Ok(())
     }
 
@@ -319,20 +578,22 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::BroadcastPsbtParams,
         mut results: server_capnp::server::BroadcastPsbtResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("broadcastPsbt");
+        self.require(Tier::Spender)?;
         let p = params.get()?;
         let path_arg = p.get_path()?.to_string()?;
         let finalize = p.get_finalize();
         let path = if path_arg.is_empty() {
-            self.layout.root.join("tx.psbt")
+            self.chain.layout.root.join("tx.psbt")
         } else {
             PathBuf::from(path_arg)
         };
-        let bytes = std::fs::read(&path)
-            .map_err(|e| failed(format!("read {}: {e}", path.display())))?;
-        let mut psbt = Psbt::deserialize(&bytes)
-            .map_err(|e| failed(format!("decode psbt: {e}")))?;
+        let bytes =
+            std::fs::read(&path).map_err(|e| failed(format!("read {}: {e}", path.display())))?;
+        let mut psbt =
+            Psbt::deserialize(&bytes).map_err(|e| failed(format!("decode psbt: {e}")))?;
         if finalize {
-            let mut state = self.state.lock().unwrap();
+            let mut state = self.chain.state.lock().unwrap();
             let entry = state
                 .active_entry_mut()
                 .ok_or_else(|| failed("no active wallet"))?;
@@ -353,12 +614,10 @@ impl server_capnp::server::Server for IpcInterface {
         let tx = psbt
             .extract_tx()
             .map_err(|e| failed(format!("extract: {e}")))?;
-        let txid = tx.compute_txid().to_string();
-        self.requester()?
-            .submit_package(tx)
-            .await
-            .map_err(|e| failed(format!("broadcast: {e}")))?;
-        results.get().set_txid(txid.as_str());
+        let (txid, route) = self.broadcast(tx).await?;
+        let mut r = results.get();
+        r.set_txid(txid.as_str());
+        r.set_route(route.as_str());
         Ok(())
     }
 
@@ -367,6 +626,7 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::HeightParams,
         mut results: server_capnp::server::HeightResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("height");
         let tip = self
             .requester()?
             .chain_tip()
@@ -381,6 +641,7 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::PeersParams,
         mut results: server_capnp::server::PeersResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("peers");
         let peers = self
             .requester()?
             .peer_info()
@@ -398,10 +659,13 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::SyncProgressParams,
         mut results: server_capnp::server::SyncProgressResults,
     ) -> Result<(), capnp::Error> {
-        let p = *self.progress.lock().unwrap();
+        let _timer = self.metrics.ipc_call("syncProgress");
+        let p = *self.chain.progress.lock().unwrap();
+        let health = self.chain.health.lock().unwrap().clone();
         let mut r = results.get();
         r.set_percent(p.unwrap_or(0.0));
         r.set_has_data(p.is_some());
+        r.set_degraded(health.degraded.as_deref().unwrap_or(""));
         Ok(())
     }
 
@@ -410,6 +674,8 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::BuildTransactionParams,
         mut results: server_capnp::server::BuildTransactionResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("buildTransaction");
+        self.require(Tier::Spender)?;
         let p = params.get()?;
         let recipient = p.get_recipient()?.to_string()?;
         let sats = p.get_sats();
@@ -423,21 +689,34 @@ impl server_capnp::server::Server for IpcInterface {
         let fee_rate = FeeRate::from_sat_per_kwu((sat_per_vb * 250.0).round() as u64);
 
         let out_path = if out_arg.is_empty() {
-            self.layout.root.join("tx.psbt")
+            self.chain.layout.root.join("tx.psbt")
         } else {
             PathBuf::from(out_arg)
         };
 
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.chain.state.lock().unwrap();
         let entry = state
             .active_entry_mut()
             .ok_or_else(|| failed("no active wallet"))?;
         let network = entry.wallet.network();
 
-        let address = Address::from_str(&recipient)
-            .map_err(|e| failed(format!("address: {e}")))?
-            .require_network(network)
-            .map_err(|e| failed(format!("address network: {e}")))?;
+        let (address, sats, label) = if bip21::is_uri(&recipient) {
+            let uri = bip21::parse(&recipient, network).map_err(|e| failed(format!("uri: {e}")))?;
+            let sats = match uri.amount.map(|a| a.to_sat()) {
+                Some(a) if sats != 0 && sats != a && !drain => {
+                    return Err(failed(format!("sats {sats} conflicts with URI amount {a}")))
+                }
+                Some(a) if sats == 0 => a,
+                _ => sats,
+            };
+            (uri.address, sats, uri.label.unwrap_or_default())
+        } else {
+            let address = Address::from_str(&recipient)
+                .map_err(|e| failed(format!("address: {e}")))?
+                .require_network(network)
+                .map_err(|e| failed(format!("address network: {e}")))?;
+            (address, sats, String::new())
+        };
         let spk = address.script_pubkey();
 
         let mut psbt = {
@@ -465,6 +744,14 @@ impl server_capnp::server::Server for IpcInterface {
             .map_err(|e| failed(format!("sign: {e}")))?;
         drop(state);
 
+        let warning = self
+            .chain
+            .fees
+            .lock()
+            .unwrap()
+            .estimates()
+            .and_then(|e| e.check(fee_rate))
+            .unwrap_or_default();
         let fee_sats = psbt.fee().map(|a| a.to_sat()).unwrap_or(0);
         let psbt_bytes = psbt.serialize();
 
@@ -472,7 +759,10 @@ impl server_capnp::server::Server for IpcInterface {
             let tx = psbt
                 .extract_tx()
                 .map_err(|e| failed(format!("extract: {e}")))?;
-            (tx.compute_txid().to_string(), consensus::encode::serialize(&tx))
+            (
+                tx.compute_txid().to_string(),
+                consensus::encode::serialize(&tx),
+            )
         } else {
             (psbt.unsigned_tx.compute_txid().to_string(), Vec::new())
         };
@@ -491,6 +781,8 @@ impl server_capnp::server::Server for IpcInterface {
         r.set_txid(txid.as_str());
         r.set_raw_tx(&raw_tx_bytes);
         r.set_fee_sats(fee_sats);
+        r.set_warning(warning.as_str());
+        r.set_label(label.as_str());
         Ok(())
     }
 
@@ -499,6 +791,8 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::ImportWalletParams,
         mut results: server_capnp::server::ImportWalletResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("importWallet");
+        self.require(Tier::Admin)?;
         let json = params.get()?.get_json()?.to_string()?;
         let backup =
             WalletBackup::from_json(&json).map_err(|e| failed(format!("parse json: {e}")))?;
@@ -516,17 +810,27 @@ impl server_capnp::server::Server for IpcInterface {
             )));
         }
 
-        let json_path = self.layout.wallets_dir().join(format!("{name}.json"));
+        // Backups that don't name a network go to this connection's.
+        let chain = match backup.network {
+            Some(n) => self.chains.iter().find(|c| c.network == n).ok_or_else(|| {
+                failed(format!(
+                    "backup is for {n}, which is not among this daemon's networks"
+                ))
+            })?,
+            None => &self.chain,
+        };
+
+        let json_path = chain.layout.wallets_dir().join(format!("{name}.json"));
         if json_path.exists() {
             return Err(failed(format!("{} already exists", json_path.display())));
         }
 
-        let mut state = self.state.lock().unwrap();
+        let mut state = chain.state.lock().unwrap();
         if state.wallets.contains_key(&name) {
             return Err(failed(format!("wallet '{name}' already loaded")));
         }
 
-        let entry = wallet::build_entry(&name, backup.clone(), &self.layout, self.network)
+        let entry = wallet::build_entry(&name, backup.clone(), &chain.layout, chain.network)
             .map_err(|e| failed(format!("build wallet: {e}")))?;
 
         let canonical = backup
@@ -539,9 +843,10 @@ impl server_capnp::server::Server for IpcInterface {
         if state.active.is_none() {
             state.active = Some(name.clone());
         }
+        let count = state.wallets.len();
         drop(state);
 
-        if let Err(e) = self.rebuild_tx.send(()).await {
+        if let Err(e) = self.rebuild_tx.send(chain.network).await {
             return Err(failed(format!("rebuild signal: {e}")));
         }
 
@@ -550,8 +855,8 @@ impl server_capnp::server::Server for IpcInterface {
         r.set_name(name.as_str());
         r.set_message(
             format!(
-                "imported '{name}'; light client rebuilding (wallet count {})",
-                self.state.lock().unwrap().wallets.len()
+                "imported '{name}' on {}; light client rebuilding (wallet count {count})",
+                chain.network
             )
             .as_str(),
         );
@@ -563,6 +868,8 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::AddPeerParams,
         mut results: server_capnp::server::AddPeerResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("addPeer");
+        self.require(Tier::Admin)?;
         let p = params.get()?;
         let ip_str = p.get_ip()?.to_string()?;
         let port = p.get_port();
@@ -570,18 +877,12 @@ impl server_capnp::server::Server for IpcInterface {
             .parse()
             .map_err(|e| failed(format!("invalid ip '{ip_str}': {e}")))?;
         let port = if port == 0 {
-            match self.network {
-                bdk_wallet::bitcoin::Network::Bitcoin => 8333,
-                bdk_wallet::bitcoin::Network::Testnet => 18333,
-                bdk_wallet::bitcoin::Network::Signet => 38333,
-                bdk_wallet::bitcoin::Network::Regtest => 18444,
-                _ => 8333,
-            }
+            sync::default_port(self.chain.network)
         } else {
             port
         };
         let sock = std::net::SocketAddr::new(ip, port);
-        self.trusted_peers.lock().unwrap().push(sock);
+        self.chain.trusted_peers.lock().unwrap().push(sock);
         let mut r = results.get();
         match self.requester() {
             Ok(req) => match req.add_peer(sock) {
@@ -609,21 +910,26 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::SetRequiredPeersParams,
         mut results: server_capnp::server::SetRequiredPeersResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("setRequiredPeers");
+        self.require(Tier::Admin)?;
         let n = params.get()?.get_num();
         let clamped = n.clamp(1, 15);
         *self.required_peers.lock().unwrap() = clamped;
         let mut r = results.get();
-        if self.requester.lock().unwrap().is_some() {
-            if let Err(e) = self.rebuild_tx.send(()).await {
+        match self.rebuild_running().await {
+            Err(e) => {
                 r.set_ok(false);
                 r.set_message(format!("rebuild signal: {e}").as_str());
                 return Ok(());
             }
-            r.set_ok(true);
-            r.set_message(format!("required peers set to {clamped}; rebuilding").as_str());
-        } else {
-            r.set_ok(true);
-            r.set_message(format!("required peers set to {clamped}").as_str());
+            Ok(0) => {
+                r.set_ok(true);
+                r.set_message(format!("required peers set to {clamped}").as_str());
+            }
+            Ok(_) => {
+                r.set_ok(true);
+                r.set_message(format!("required peers set to {clamped}; rebuilding").as_str());
+            }
         }
         Ok(())
     }
@@ -633,14 +939,11 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::NetworkParams,
         mut results: server_capnp::server::NetworkResults,
     ) -> Result<(), capnp::Error> {
-        let name = match self.network {
-            bdk_wallet::bitcoin::Network::Bitcoin => "bitcoin",
-            bdk_wallet::bitcoin::Network::Testnet => "testnet",
-            bdk_wallet::bitcoin::Network::Signet => "signet",
-            bdk_wallet::bitcoin::Network::Regtest => "regtest",
-            other => return Err(failed(format!("unknown network {other:?}"))),
-        };
-        results.get().set_name(name);
+        let _timer = self.metrics.ipc_call("network");
+        // The same names `network`, `extra_networks` and `useNetwork` take.
+        results
+            .get()
+            .set_name(self.chain.network.to_string().as_str());
         Ok(())
     }
 
@@ -649,6 +952,7 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::GetRequiredPeersParams,
         mut results: server_capnp::server::GetRequiredPeersResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("getRequiredPeers");
         let n = *self.required_peers.lock().unwrap();
         results.get().set_num(n);
         Ok(())
@@ -659,6 +963,8 @@ impl server_capnp::server::Server for IpcInterface {
         params: server_capnp::server::SetTorProxyParams,
         mut results: server_capnp::server::SetTorProxyResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("setTorProxy");
+        self.require(Tier::Admin)?;
         let p = params.get()?;
         let enabled = p.get_enabled();
         let new_value = if enabled {
@@ -674,23 +980,32 @@ impl server_capnp::server::Server for IpcInterface {
         };
         *self.tor_proxy.lock().unwrap() = new_value;
         let mut r = results.get();
-        if self.requester.lock().unwrap().is_some() {
-            if let Err(e) = self.rebuild_tx.send(()).await {
+        match self.rebuild_running().await {
+            Err(e) => {
                 r.set_ok(false);
                 r.set_message(format!("rebuild signal: {e}").as_str());
                 return Ok(());
             }
-            r.set_ok(true);
-            r.set_message(match new_value {
-                Some(a) => format!("routing through Socks5 proxy {a}; rebuilding"),
-                None => "Socks5 proxy disabled; rebuilding".to_string(),
-            }.as_str());
-        } else {
-            r.set_ok(true);
-            r.set_message(match new_value {
-                Some(a) => format!("Socks5 proxy set to {a}"),
-                None => "Socks5 proxy disabled".to_string(),
-            }.as_str());
+            Ok(0) => {
+                r.set_ok(true);
+                r.set_message(
+                    match new_value {
+                        Some(a) => format!("Socks5 proxy set to {a}"),
+                        None => "Socks5 proxy disabled".to_string(),
+                    }
+                    .as_str(),
+                );
+            }
+            Ok(_) => {
+                r.set_ok(true);
+                r.set_message(
+                    match new_value {
+                        Some(a) => format!("routing through Socks5 proxy {a}; rebuilding"),
+                        None => "Socks5 proxy disabled; rebuilding".to_string(),
+                    }
+                    .as_str(),
+                );
+            }
         }
         Ok(())
     }
@@ -700,6 +1015,7 @@ impl server_capnp::server::Server for IpcInterface {
         _: server_capnp::server::GetTorProxyParams,
         mut results: server_capnp::server::GetTorProxyResults,
     ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("getTorProxy");
         let current = *self.tor_proxy.lock().unwrap();
         let mut r = results.get();
         match current {
@@ -716,4 +1032,415 @@ impl server_capnp::server::Server for IpcInterface {
         }
         Ok(())
     }
+
+    async fn estimate_fee(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::EstimateFeeParams,
+        mut results: server_capnp::server::EstimateFeeResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("estimateFee");
+        let target = params.get()?.get_target_blocks();
+        let cache = self.chain.fees.lock().unwrap();
+        let mut r = results.get();
+        match (cache.estimates(), cache.for_target(target)) {
+            (Some(e), Some(rate)) => {
+                r.set_sat_per_vb(fees::sat_per_vb(rate));
+                r.set_low(fees::sat_per_vb(e.low));
+                r.set_medium(fees::sat_per_vb(e.medium));
+                r.set_high(fees::sat_per_vb(e.high));
+                r.set_blocks(e.blocks);
+                r.set_has_data(true);
+            }
+            _ => r.set_has_data(false),
+        }
+        Ok(())
+    }
+
+    async fn list_pending(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::ListPendingParams,
+        mut results: server_capnp::server::ListPendingResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("listPending");
+        let pending = self.chain.pending.lock().unwrap();
+        let mut list = results.get().init_entries(pending.len() as u32);
+        for (i, (txid, p)) in pending.iter().enumerate() {
+            let mut row = list.reborrow().get(i as u32);
+            row.set_txid(txid.to_string().as_str());
+            row.set_attempts(p.attempts);
+            row.set_first_broadcast(p.first_broadcast);
+            row.set_last_broadcast(p.last_broadcast);
+            row.set_next_broadcast(pending.next_broadcast(p).unwrap_or(0));
+            row.set_last_error(p.last_error.as_deref().unwrap_or(""));
+        }
+        Ok(())
+    }
+
+    async fn authenticate(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::AuthenticateParams,
+        mut results: server_capnp::server::AuthenticateResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("authenticate");
+        if self.remote {
+            warn!(target: "ipc", "authenticate: refused on a remote connection");
+            return Err(failed(
+                "remote connections keep the tier they were paired at",
+            ));
+        }
+        let token = params.get()?.get_token()?.to_string()?;
+        let tier = self.cookies.check(token.trim()).ok_or_else(|| {
+            warn!(target: "ipc", "authenticate: bad token");
+            failed("invalid token")
+        })?;
+        let mut upgraded = (*self).clone();
+        let tier = tier.max(self.tier);
+        upgraded.tier = tier;
+        debug!(target: "ipc", "connection upgraded to {tier}");
+        let mut r = results.get();
+        r.set_server(capnp_rpc::new_client(upgraded));
+        r.set_tier(tier.to_string().as_str());
+        Ok(())
+    }
+
+    async fn status(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::StatusParams,
+        mut results: server_capnp::server::StatusResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("status");
+        let requester = self.chain.requester.lock().unwrap().clone();
+        let (height, peers) = match requester {
+            Some(r) => (
+                r.chain_tip().await.ok().map(|t| t.height),
+                r.peer_info().await.map(|p| p.len() as u32).unwrap_or(0),
+            ),
+            None => (None, 0),
+        };
+        let progress = *self.chain.progress.lock().unwrap();
+        let wallets = self.chain.state.lock().unwrap().wallets.len() as u32;
+        let health = self.chain.health.lock().unwrap().clone();
+        let mut s = results.get().init_status();
+        s.set_pid(std::process::id());
+        s.set_uptime_secs(self.started.elapsed().as_secs());
+        s.set_network(self.chain.network.to_string().as_str());
+        s.set_height(height.unwrap_or(0));
+        s.set_has_height(height.is_some());
+        s.set_percent(progress.unwrap_or(0.0));
+        s.set_has_progress(progress.is_some());
+        s.set_peers(peers);
+        s.set_required_peers(*self.required_peers.lock().unwrap());
+        s.set_wallets(wallets);
+        s.set_degraded(health.degraded.as_deref().unwrap_or(""));
+        s.set_restarts(health.restarts);
+        Ok(())
+    }
+
+    async fn reload_config(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::ReloadConfigParams,
+        mut results: server_capnp::server::ReloadConfigResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("reloadConfig");
+        self.require(Tier::Admin)?;
+        let (tx, rx) = oneshot::channel();
+        self.reload_tx
+            .send(tx)
+            .await
+            .map_err(|e| failed(format!("reload signal: {e}")))?;
+        let report = rx
+            .await
+            .map_err(|_| failed("daemon dropped the reload request"))?;
+        let mut r = results.get();
+        let mut applied = r.reborrow().init_applied(report.applied.len() as u32);
+        for (i, m) in report.applied.iter().enumerate() {
+            applied.set(i as u32, m.as_str());
+        }
+        let mut rejected = r.init_rejected(report.rejected.len() as u32);
+        for (i, m) in report.rejected.iter().enumerate() {
+            rejected.set(i as u32, m.as_str());
+        }
+        Ok(())
+    }
+
+    async fn set_log_level(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::SetLogLevelParams,
+        mut results: server_capnp::server::SetLogLevelResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("setLogLevel");
+        self.require(Tier::Admin)?;
+        let directives = params.get()?.get_directives()?.to_string()?;
+        let directives = directives.trim();
+        let mut r = results.get();
+        if directives.is_empty() {
+            r.set_ok(true);
+        } else {
+            match self.log.set(directives) {
+                Ok(()) => {
+                    info!(target: "ipc", "log level set to '{directives}'");
+                    r.set_ok(true);
+                    r.set_message(format!("log level set to '{directives}'").as_str());
+                }
+                Err(e) => {
+                    r.set_ok(false);
+                    r.set_message(e.as_str());
+                }
+            }
+        }
+        r.set_current(self.log.current().as_str());
+        Ok(())
+    }
+
+    async fn tail_logs(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::TailLogsParams,
+        mut results: server_capnp::server::TailLogsResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("tailLogs");
+        self.require(Tier::Admin)?;
+        if self.logs.capacity() == 0 {
+            return Err(failed("log buffer is disabled (log_buffer = 0)"));
+        }
+        let p = params.get()?;
+        let level = p.get_from_level()?.to_string()?;
+        let min = match level.trim() {
+            "" => tracing::Level::TRACE,
+            l => l
+                .parse()
+                .map_err(|_| failed(format!("unknown level '{l}'")))?,
+        };
+        let targets = p
+            .get_targets()?
+            .iter()
+            .map(|t| Ok(t?.to_string()?))
+            .collect::<Result<Vec<_>, capnp::Error>>()?;
+        let filter = LogFilter { min, targets };
+        let (tail, closed) = oneshot::channel();
+        tokio::task::spawn_local(forward_logs(
+            self.logs.clone(),
+            filter,
+            p.get_backlog() as usize,
+            p.get_sink()?,
+            closed,
+        ));
+        results
+            .get()
+            .set_tail(capnp_rpc::new_client(LogTail { _tail: tail }));
+        Ok(())
+    }
+
+    async fn reorgs(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::ReorgsParams,
+        mut results: server_capnp::server::ReorgsResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("reorgs");
+        let reorgs = self.chain.reorgs.lock().unwrap();
+        let mut list = results.get().init_entries(reorgs.len() as u32);
+        for (i, r) in reorgs.iter().enumerate() {
+            let mut row = list.reborrow().get(i as u32);
+            row.set_time(r.time);
+            row.set_depth(r.depth());
+            row.set_common_height(r.common.height);
+            row.set_common_hash(r.common.hash.to_string().as_str());
+            row.set_old_tip_height(r.old_tip.height);
+            row.set_old_tip_hash(r.old_tip.hash.to_string().as_str());
+            row.set_new_tip_height(r.new_tip.height);
+            row.set_new_tip_hash(r.new_tip.hash.to_string().as_str());
+            let mut wallets = row.reborrow().init_wallets(r.wallets.len() as u32);
+            for (j, w) in r.wallets.iter().enumerate() {
+                wallets.set(j as u32, w.as_str());
+            }
+            let mut moved = row.init_moved(r.moved.len() as u32);
+            for (j, m) in r.moved.iter().enumerate() {
+                let mut tx = moved.reborrow().get(j as u32);
+                tx.set_wallet(m.wallet.as_str());
+                tx.set_txid(m.txid.to_string().as_str());
+                tx.set_from_height(m.from);
+                tx.set_to_height(m.to.unwrap_or(0));
+            }
+        }
+        Ok(())
+    }
+
+    async fn wallet_status(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::WalletStatusParams,
+        mut results: server_capnp::server::WalletStatusResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("walletStatus");
+        let name = params.get()?.get_name()?.to_string()?;
+        let state = self.chain.state.lock().unwrap();
+        let entry = state
+            .wallets
+            .get(&name)
+            .ok_or_else(|| failed(format!("no wallet named {name}")))?;
+        let tip = entry.wallet.latest_checkpoint();
+        let external = entry.wallet.derivation_index(KeychainKind::External);
+        let internal = entry.wallet.derivation_index(KeychainKind::Internal);
+        let mut s = results.get().init_status();
+        s.set_name(name.as_str());
+        s.set_height(tip.height());
+        s.set_hash(tip.hash().to_string().as_str());
+        s.set_recovery(entry.recovery_from.is_some());
+        s.set_recovery_from(entry.recovery_from.unwrap_or(0));
+        s.set_external_revealed(external.unwrap_or(0));
+        s.set_has_external(external.is_some());
+        s.set_internal_revealed(internal.unwrap_or(0));
+        s.set_has_internal(internal.is_some());
+        s.set_last_update(entry.last_update.unwrap_or(0));
+        s.set_transactions(entry.wallet.transactions().count() as u32);
+        Ok(())
+    }
+
+    async fn sync_state(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::SyncStateParams,
+        mut results: server_capnp::server::SyncStateResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("syncState");
+        let requester = self.chain.requester.lock().unwrap().clone();
+        let height = match requester {
+            Some(r) => r.chain_tip().await.ok().map(|t| t.height),
+            None => None,
+        };
+        let percent = *self.chain.progress.lock().unwrap();
+        let details = self.chain.details.lock().unwrap();
+        let mut s = results.get().init_state();
+        s.set_header_height(height.unwrap_or(0));
+        s.set_has_headers(height.is_some());
+        if let Some(stages) = details.stages {
+            s.set_filter_headers(stages.filter_headers);
+            s.set_filters(stages.filters);
+            s.set_total_to_check(stages.total);
+            s.set_has_progress(true);
+        }
+        s.set_percent(percent.unwrap_or(0.0));
+        s.set_blocks_fetched(details.blocks);
+        let eta = details.eta();
+        s.set_eta_secs(eta.map_or(0, |d| d.as_secs()));
+        s.set_has_eta(eta.is_some());
+        if let Some((height, time)) = details.peer_tip {
+            s.set_peer_tip(height);
+            s.set_has_peer_tip(true);
+            s.set_peer_tip_time(time);
+        }
+        let mut warnings = s.init_warnings(details.warnings.len() as u32);
+        for (i, (time, message)) in details.warnings.iter().enumerate() {
+            let mut w = warnings.reborrow().get(i as u32);
+            w.set_time(*time);
+            w.set_message(message.as_str());
+        }
+        Ok(())
+    }
+
+    async fn networks(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::NetworksParams,
+        mut results: server_capnp::server::NetworksResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("networks");
+        let mut list = results.get().init_names(self.chains.len() as u32);
+        for (i, chain) in self.chains.iter().enumerate() {
+            list.set(i as u32, chain.network.to_string().as_str());
+        }
+        Ok(())
+    }
+
+    async fn use_network(
+        self: capnp::capability::Rc<Self>,
+        params: server_capnp::server::UseNetworkParams,
+        mut results: server_capnp::server::UseNetworkResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("useNetwork");
+        let name = params.get()?.get_name()?.to_string()?;
+        let network =
+            Network::from_str(name.trim()).map_err(|e| failed(format!("network '{name}': {e}")))?;
+        let chain = self
+            .chains
+            .iter()
+            .find(|c| c.network == network)
+            .ok_or_else(|| failed(format!("{network} is not among this daemon's networks")))?;
+        let mut bound = (*self).clone();
+        bound.chain = chain.clone();
+        debug!(target: "ipc", "connection switched to {network}");
+        results.get().set_server(capnp_rpc::new_client(bound));
+        Ok(())
+    }
+
+    async fn broadcasts(
+        self: capnp::capability::Rc<Self>,
+        _: server_capnp::server::BroadcastsParams,
+        mut results: server_capnp::server::BroadcastsResults,
+    ) -> Result<(), capnp::Error> {
+        let _timer = self.metrics.ipc_call("broadcasts");
+        let history = self.chain.broadcaster.history().lock().unwrap();
+        let mut list = results.get().init_entries(history.len() as u32);
+        for (i, b) in history.iter().enumerate() {
+            let mut row = list.reborrow().get(i as u32);
+            row.set_time(b.time);
+            row.set_txid(b.txid.to_string().as_str());
+            row.set_source(b.source.label());
+            row.set_via(b.via.as_str());
+            row.set_delay_secs(b.delay_secs);
+            row.set_ok(b.error.is_none());
+            row.set_error(b.error.as_deref().unwrap_or(""));
+        }
+        Ok(())
+    }
+}
+
+/// Dropped (ending the tail) when the client releases its capability.
+struct LogTail {
+    _tail: oneshot::Sender<()>,
+}
+
+impl server_capnp::log_tail::Server for LogTail {}
+
+// Nothing in here may log: every line would wake the tail up again.
+async fn forward_logs(
+    logs: LogBuffer,
+    filter: LogFilter,
+    backlog: usize,
+    sink: server_capnp::log_sink::Client,
+    mut closed: oneshot::Receiver<()>,
+) {
+    let mut latest = logs.subscribe();
+    let mut slice = logs.recent(&filter, backlog);
+    loop {
+        let last = slice.last;
+        if !slice.entries.is_empty() || slice.missed > 0 {
+            tokio::select! {
+                sent = push_logs(&sink, slice) => if sent.is_err() { return },
+                _ = &mut closed => return,
+            }
+        }
+        while *latest.borrow_and_update() <= last {
+            tokio::select! {
+                changed = latest.changed() => if changed.is_err() { return },
+                _ = &mut closed => return,
+            }
+        }
+        slice = logs.since(last, &filter, LOG_BATCH);
+    }
+}
+
+async fn push_logs(
+    sink: &server_capnp::log_sink::Client,
+    slice: Slice,
+) -> Result<(), capnp::Error> {
+    let mut req = sink.push_request();
+    let mut p = req.get();
+    p.set_missed(slice.missed);
+    let mut list = p.init_entries(slice.entries.len() as u32);
+    for (i, e) in slice.entries.iter().enumerate() {
+        let mut row = list.reborrow().get(i as u32);
+        row.set_seq(e.seq);
+        row.set_time_ms(e.time_ms);
+        row.set_level(e.level.as_str());
+        row.set_target(e.target.as_str());
+        row.set_message(e.message.as_str());
+    }
+    req.send().promise.await.map(|_| ())
 }
@@ -1,8 +1,30 @@
 pub use bdk_kyoto::bip157::tokio;
+pub mod access;
+pub mod auth;
+pub mod bip21;
+pub mod broadcast;
+pub mod chain;
+pub mod checkpoints;
+pub mod clock;
+pub mod control;
 pub mod daemonize;
+pub mod fees;
+pub mod hooks;
 pub mod ipc;
+pub mod lock;
+pub mod logbuf;
+pub mod logging;
+pub mod metrics;
 pub mod paths;
+pub mod peer;
+pub mod random;
+pub mod rebroadcast;
+pub mod remote;
+pub mod reorg;
 pub mod sync;
+pub mod systemd;
 pub mod wallet;
+pub mod watch;
+pub mod webhooks;
 
 capnp::generated_code!(pub mod server_capnp);
@@ -0,0 +1,103 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use tracing::info;
+
+use crate::paths::Layout;
+
+#[derive(Debug)]
+pub enum LockError {
+    Io(io::Error),
+    Held { pid: Option<u32> },
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "datadir lock: {e}"),
+            Self::Held { pid: Some(pid) } => {
+                write!(f, "datadir is in use by another kyotod (pid {pid})")
+            }
+            Self::Held { pid: None } => write!(f, "datadir is in use by another kyotod"),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+/// An advisory `flock` on `<datadir>/kyotod.lock`, held for the life of the
+/// process. The kernel drops it when the process exits, however it exits, so
+/// whatever socket or pid file is lying around when we acquire it was left
+/// behind by a daemon that is no longer running.
+pub struct DatadirLock {
+    _file: File,
+}
+
+impl DatadirLock {
+    pub fn acquire(layout: &Layout) -> Result<Self, LockError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(layout.lock_file())
+            .map_err(LockError::Io)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == -1 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+                return Err(LockError::Held {
+                    pid: read_pid(&layout.pid_file()),
+                });
+            }
+            return Err(LockError::Io(err));
+        }
+        Ok(Self { _file: file })
+    }
+
+    /// Remove the pid file and socket a crashed daemon left behind. Only
+    /// meaningful while the lock is held, hence a method.
+    pub fn clear_stale(&self, layout: &Layout) -> io::Result<()> {
+        self.clear_stale_pid(layout)?;
+        clear_stale_socket(&layout.socket())
+    }
+
+    /// Like `clear_stale`, but leaves the socket alone. Used under socket
+    /// activation, where the socket belongs to the service manager.
+    pub fn clear_stale_pid(&self, layout: &Layout) -> io::Result<()> {
+        let pid = layout.pid_file();
+        if pid.exists() {
+            info!(
+                target: "node",
+                "removing stale pid file {} (pid {:?})",
+                pid.display(),
+                read_pid(&pid)
+            );
+            fs::remove_file(&pid)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove `path` if it is a socket nobody is listening on. Fails with
+/// `AddrInUse` if a live server still answers on it, which can happen when
+/// two datadirs are pointed at the same `--socket`.
+pub fn clear_stale_socket(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by a running server", path.display()),
+        ));
+    }
+    info!(target: "node", "removing stale socket {}", path.display());
+    fs::remove_file(path)
+}
+
+pub fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
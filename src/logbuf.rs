@@ -0,0 +1,193 @@
+//! The most recent log lines, kept in memory for `tailLogs`. Only events
+//! that pass the active `log_level` filter reach the buffer.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bdk_kyoto::bip157::tokio;
+use tokio::sync::watch;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+pub type LogBuffer = Arc<LogRing>;
+
+#[derive(Clone)]
+pub struct LogEntry {
+    /// Increases by one per event, starting at 1.
+    pub seq: u64,
+    pub time_ms: u64,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// Which entries a tail wants: `min` or more severe, from any of `targets`
+/// (and their `::` children), or from everywhere when `targets` is empty.
+pub struct LogFilter {
+    pub min: Level,
+    pub targets: Vec<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, e: &LogEntry) -> bool {
+        // tracing orders levels by verbosity: ERROR < WARN < ... < TRACE.
+        e.level <= self.min
+            && (self.targets.is_empty()
+                || self.targets.iter().any(|t| {
+                    e.target == *t
+                        || e.target
+                            .strip_prefix(t.as_str())
+                            .is_some_and(|rest| rest.starts_with("::"))
+                }))
+    }
+}
+
+/// A batch of entries after some sequence number.
+pub struct Slice {
+    pub entries: Vec<LogEntry>,
+    /// Entries of any level that were evicted before the reader got to them.
+    pub missed: u64,
+    /// Pass this as `after` next time.
+    pub last: u64,
+}
+
+pub struct LogRing {
+    entries: Mutex<VecDeque<LogEntry>>,
+    capacity: usize,
+    latest: watch::Sender<u64>,
+}
+
+impl LogRing {
+    pub fn new(capacity: usize) -> LogBuffer {
+        Arc::new(Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            latest: watch::Sender::new(0),
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The tracing layer that fills this buffer.
+    pub fn layer(self: &Arc<Self>) -> RingLayer {
+        RingLayer(self.clone())
+    }
+
+    /// Changes whenever an entry is added.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.latest.subscribe()
+    }
+
+    /// Up to `n` of the newest entries matching `filter`.
+    pub fn recent(&self, filter: &LogFilter, n: usize) -> Slice {
+        let entries = self.entries.lock().unwrap();
+        let mut out: Vec<LogEntry> = entries
+            .iter()
+            .rev()
+            .filter(|e| filter.matches(e))
+            .take(n)
+            .cloned()
+            .collect();
+        out.reverse();
+        Slice {
+            entries: out,
+            missed: 0,
+            last: entries.back().map_or(0, |e| e.seq),
+        }
+    }
+
+    /// Entries matching `filter` with a sequence number above `after`, at
+    /// most `max` of them.
+    pub fn since(&self, after: u64, filter: &LogFilter, max: usize) -> Slice {
+        let entries = self.entries.lock().unwrap();
+        let oldest = entries.front().map_or(after + 1, |e| e.seq);
+        let missed = oldest.saturating_sub(after + 1);
+        let mut last = after.max(oldest.saturating_sub(1));
+        let mut out = Vec::new();
+        for e in entries.iter().skip_while(|e| e.seq <= after) {
+            if out.len() == max {
+                break;
+            }
+            last = e.seq;
+            if filter.matches(e) {
+                out.push(e.clone());
+            }
+        }
+        Slice {
+            entries: out,
+            missed,
+            last,
+        }
+    }
+
+    fn push(&self, level: Level, target: &str, message: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let seq = {
+            let mut entries = self.entries.lock().unwrap();
+            let seq = entries.back().map_or(1, |e| e.seq + 1);
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(LogEntry {
+                seq,
+                time_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64),
+                level,
+                target: target.to_string(),
+                message,
+            });
+            seq
+        };
+        self.latest.send_replace(seq);
+    }
+}
+
+pub struct RingLayer(LogBuffer);
+
+impl<S: Subscriber> Layer<S> for RingLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut line = Line(String::new());
+        event.record(&mut line);
+        self.0.push(*meta.level(), meta.target(), line.0);
+    }
+}
+
+// The message first, then any other fields as `key=value`, like the text
+// formatter.
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let rest = std::mem::take(&mut self.0);
+            let _ = write!(self.0, "{value:?}");
+            if !rest.is_empty() {
+                self.0.push(' ');
+                self.0.push_str(&rest);
+            }
+        } else {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            let _ = write!(self.0, "{}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.record_debug(field, &format_args!("{value}"));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Tracing setup: per-target level filters that can be changed at runtime,
+//! text or JSON output, and an optional size/time-rotated log file.
+
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::{warn, Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::time::{self as fmt_time, FormatTime};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Layer, Registry};
+
+use crate::clock::unix_now;
+
+pub const DEFAULT_DIRECTIVES: &str = "info";
+
+/// The subscriber every output layer is stacked on.
+pub type Base = Layered<reload::Layer<Targets, Registry>, Registry>;
+pub type BoxedLayer = Box<dyn Layer<Base> + Send + Sync>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown log format '{other}' (text, json)")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl Period {
+    fn secs(self) -> Option<u64> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => Some(60 * 60),
+            Self::Daily => Some(24 * 60 * 60),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            other => Err(format!("unknown rotation '{other}' (never, hourly, daily)")),
+        }
+    }
+}
+
+/// When to start a new log file and how many old ones to keep as
+/// `<file>.1` (newest) through `<file>.<keep>`.
+#[derive(Clone, Copy)]
+pub struct Rotation {
+    pub max_bytes: u64,
+    pub period: Period,
+    pub keep: u32,
+}
+
+pub struct LogFile {
+    pub path: PathBuf,
+    pub rotation: Rotation,
+    /// Point stdout and stderr at the new file after every rotation, so
+    /// panics and stray prints from a daemonized process follow the log.
+    /// `Daemonize` does the initial redirect.
+    pub redirect_stdio: bool,
+}
+
+pub struct Options {
+    pub directives: String,
+    pub format: Format,
+    pub stdout: bool,
+    pub file: Option<LogFile>,
+}
+
+/// Changes the active filter directives of a running process.
+#[derive(Clone)]
+pub struct LogControl {
+    handle: reload::Handle<Targets, Registry>,
+    current: Arc<Mutex<String>>,
+    rotation_error: RotationError,
+}
+
+// The file writer runs inside the subscriber, where emitting an event would
+// re-enter it. It parks the failure here for the daemon to log.
+type RotationError = Arc<Mutex<Option<String>>>;
+
+impl LogControl {
+    /// Apply directives like `info,node=debug,ipc=warn`.
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        let targets = parse(directives)?;
+        self.handle.reload(targets).map_err(|e| e.to_string())?;
+        *self.current.lock().unwrap() = directives.to_string();
+        Ok(())
+    }
+
+    pub fn current(&self) -> String {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// The latest log rotation failure not yet reported, if any.
+    pub fn take_rotation_error(&self) -> Option<String> {
+        self.rotation_error.lock().unwrap().take()
+    }
+}
+
+pub fn parse(directives: &str) -> Result<Targets, String> {
+    Targets::from_str(directives).map_err(|e| format!("log level '{directives}': {e}"))
+}
+
+/// Install the global subscriber. Extra layers (such as the IPC log buffer)
+/// are stacked alongside the configured outputs.
+pub fn init(opts: Options, extra: Vec<BoxedLayer>) -> io::Result<LogControl> {
+    let (targets, directives, rejected) = match parse(&opts.directives) {
+        Ok(t) => (t, opts.directives, None),
+        Err(e) => {
+            let t = parse(DEFAULT_DIRECTIVES).expect("default directives");
+            (t, DEFAULT_DIRECTIVES.to_string(), Some(e))
+        }
+    };
+    let (filter, handle) = reload::Layer::new(targets);
+
+    let mut layers = extra;
+    if opts.stdout {
+        layers.push(output(opts.format, true, io::stdout));
+    }
+    let rotation_error = RotationError::default();
+    if let Some(file) = opts.file {
+        let writer = Mutex::new(RotatingFile::open(file, rotation_error.clone())?);
+        layers.push(output(opts.format, false, writer));
+    }
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .init();
+    if let Some(e) = rejected {
+        warn!(target: "node", "{e}; using {DEFAULT_DIRECTIVES}");
+    }
+    Ok(LogControl {
+        handle,
+        current: Arc::new(Mutex::new(directives)),
+        rotation_error,
+    })
+}
+
+fn output<W>(format: Format, ansi: bool, writer: W) -> BoxedLayer
+where
+    W: for<'w> fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer()
+        .with_target(true)
+        .with_level(true)
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match format {
+        Format::Text => layer.boxed(),
+        Format::Json => layer.event_format(Json).boxed(),
+    }
+}
+
+/// One JSON object per line: timestamp, level, target, message and any
+/// structured fields.
+struct Json;
+
+impl<S, N> FormatEvent<S, N> for Json
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut ts = String::new();
+        fmt_time::SystemTime.format_time(&mut Writer::new(&mut ts))?;
+        let meta = event.metadata();
+        let mut obj = serde_json::Map::new();
+        obj.insert("timestamp".into(), ts.into());
+        obj.insert("level".into(), meta.level().as_str().into());
+        obj.insert("target".into(), meta.target().into());
+        event.record(&mut JsonFields(&mut obj));
+        writeln!(writer, "{}", serde_json::Value::Object(obj))
+    }
+}
+
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl Visit for JsonFields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let mut s = String::new();
+        let _ = write!(s, "{value:?}");
+        self.0.insert(field.name().into(), s.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+}
+
+struct RotatingFile {
+    config: LogFile,
+    file: File,
+    size: u64,
+    next_rotation: Option<u64>,
+    error: RotationError,
+}
+
+impl RotatingFile {
+    fn open(config: LogFile, error: RotationError) -> io::Result<Self> {
+        if let Some(dir) = config.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = append(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            next_rotation: boundary(config.rotation.period),
+            config,
+            file,
+            size,
+            error,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.config.path;
+        let keep = self.config.rotation.keep;
+        if keep == 0 {
+            fs::remove_file(path)?;
+        } else {
+            for i in (1..keep).rev() {
+                let from = numbered(path, i);
+                if from.exists() {
+                    fs::rename(&from, numbered(path, i + 1))?;
+                }
+            }
+            fs::rename(path, numbered(path, 1))?;
+        }
+        self.file = append(path)?;
+        self.size = 0;
+        self.next_rotation = boundary(self.config.rotation.period);
+        self.redirect()
+    }
+
+    fn redirect(&self) -> io::Result<()> {
+        if !self.config.redirect_stdio {
+            return Ok(());
+        }
+        for target in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            if unsafe { libc::dup2(self.file.as_raw_fd(), target) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        let max = self.config.rotation.max_bytes;
+        let by_size = max > 0 && self.size > 0 && self.size + incoming as u64 > max;
+        let by_time = self.next_rotation.is_some_and(|t| unix_now() >= t);
+        by_size || by_time
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // A failed rotation must not lose the line; keep writing to the
+            // current file and try again on the next one.
+            if let Err(e) = self.rotate() {
+                let msg = format!("log rotation {}: {e}", self.config.path.display());
+                *self.error.lock().unwrap() = Some(msg);
+                self.next_rotation = boundary(self.config.rotation.period);
+            }
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(format!(".{n}"));
+    PathBuf::from(s)
+}
+
+// Rotations happen on UTC hour or day boundaries, not relative to startup.
+fn boundary(period: Period) -> Option<u64> {
+    let secs = period.secs()?;
+    Some((unix_now() / secs + 1) * secs)
+}
@@ -0,0 +1,434 @@
+//! Prometheus text exposition over plain HTTP, meant for a loopback address.
+//! Counters are kept here as they happen; chain, peer and wallet gauges are
+//! read from the shared daemon state on every scrape. Everything about a
+//! chain carries a `network` label.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::Network;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+use crate::clock::unix_now;
+use crate::ipc::RequesterSlot;
+use crate::sync::{ProgressSlot, RequiredPeers};
+use crate::wallet::State;
+
+pub type SharedMetrics = Arc<Metrics>;
+
+/// Upper bounds, in seconds, of the IPC latency histogram buckets.
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0];
+const MAX_REQUEST: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+pub enum Source {
+    Ipc,
+    Rebroadcast,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ipc => "ipc",
+            Self::Rebroadcast => "rebroadcast",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct NetworkCounts {
+    rebuilds: u64,
+    // Indexed by [Source][ok, failed].
+    broadcasts: [[u64; 2]; 2],
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    networks: Mutex<BTreeMap<String, NetworkCounts>>,
+    ipc: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl Metrics {
+    pub fn new() -> SharedMetrics {
+        Arc::new(Self::default())
+    }
+
+    pub fn rebuilt(&self, network: Network) {
+        self.counts(network, |c| c.rebuilds += 1);
+    }
+
+    pub fn broadcast(&self, network: Network, source: Source, ok: bool) {
+        self.counts(network, |c| {
+            c.broadcasts[source as usize][usize::from(!ok)] += 1
+        });
+    }
+
+    fn counts(&self, network: Network, f: impl FnOnce(&mut NetworkCounts)) {
+        f(self
+            .networks
+            .lock()
+            .unwrap()
+            .entry(network.to_string())
+            .or_default());
+    }
+
+    /// Counts an IPC call and records its latency when the guard is dropped.
+    pub fn ipc_call(&self, method: &'static str) -> CallTimer<'_> {
+        CallTimer {
+            metrics: self,
+            method,
+            start: Instant::now(),
+        }
+    }
+
+    fn observe(&self, method: &'static str, secs: f64) {
+        let mut ipc = self.ipc.lock().unwrap();
+        let h = ipc.entry(method).or_default();
+        for (bucket, bound) in h.buckets.iter_mut().zip(BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        h.count += 1;
+        h.sum += secs;
+    }
+}
+
+pub struct CallTimer<'a> {
+    metrics: &'a Metrics,
+    method: &'static str,
+    start: Instant,
+}
+
+impl Drop for CallTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .observe(self.method, self.start.elapsed().as_secs_f64());
+    }
+}
+
+/// What a scrape reads about one network.
+pub struct ChainSources {
+    pub network: Network,
+    pub state: Arc<Mutex<State>>,
+    pub requester: RequesterSlot,
+    pub progress: ProgressSlot,
+}
+
+/// Everything a scrape reads besides the counters.
+pub struct Sources {
+    pub metrics: SharedMetrics,
+    /// The primary network first.
+    pub chains: Vec<ChainSources>,
+    pub required_peers: RequiredPeers,
+    /// Export per-wallet balances. Off by default, since anyone who can
+    /// reach the endpoint can read them.
+    pub balances: bool,
+}
+
+/// Serve `GET /metrics` on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, sources: Sources) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!(target: "node", "metrics: bind {addr}: {e}");
+            return;
+        }
+    };
+    info!(target: "node", "metrics on http://{addr}/metrics");
+    let sources = Arc::new(sources);
+    loop {
+        let stream = match listener.accept().await {
+            Ok((s, _)) => s,
+            Err(e) => {
+                warn!(target: "node", "metrics: accept: {e}");
+                continue;
+            }
+        };
+        let sources = sources.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &sources).await {
+                debug!(target: "node", "metrics: {e}");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, sources: &Sources) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    // Only the request line matters; read until the end of the headers.
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut chunk))
+            .await
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out")
+            })??;
+        if n == 0 || buf.len() + n > MAX_REQUEST {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(sources).await),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn render(sources: &Sources) -> String {
+    let mut out = String::new();
+    let mut chains = Vec::new();
+    for chain in &sources.chains {
+        chains.push((chain, ChainReading::read(chain).await));
+    }
+
+    gauge(
+        &mut out,
+        "kyotod_chain_height",
+        "Height of the light client's chain tip.",
+    );
+    for (chain, r) in &chains {
+        if let Some(height) = r.height {
+            let _ = writeln!(
+                out,
+                "kyotod_chain_height{{network=\"{}\"}} {height}",
+                chain.network
+            );
+        }
+    }
+    gauge(
+        &mut out,
+        "kyotod_last_block_age_seconds",
+        "Seconds since the timestamp in the chain tip's header.",
+    );
+    for (chain, r) in &chains {
+        if let Some(time) = r.tip_time {
+            let _ = writeln!(
+                out,
+                "kyotod_last_block_age_seconds{{network=\"{}\"}} {}",
+                chain.network,
+                unix_now().saturating_sub(time)
+            );
+        }
+    }
+    gauge(
+        &mut out,
+        "kyotod_sync_progress_ratio",
+        "Sync progress from 0 to 1.",
+    );
+    for (chain, _) in &chains {
+        if let Some(p) = *chain.progress.lock().unwrap() {
+            let _ = writeln!(
+                out,
+                "kyotod_sync_progress_ratio{{network=\"{}\"}} {}",
+                chain.network,
+                f64::from(p) / 100.0
+            );
+        }
+    }
+    gauge(&mut out, "kyotod_peers_connected", "Connected peers.");
+    for (chain, r) in &chains {
+        let _ = writeln!(
+            out,
+            "kyotod_peers_connected{{network=\"{}\"}} {}",
+            chain.network,
+            r.peers.unwrap_or(0)
+        );
+    }
+    gauge(
+        &mut out,
+        "kyotod_peers_required",
+        "Peers each light client keeps connected.",
+    );
+    let required = *sources.required_peers.lock().unwrap();
+    for (chain, _) in &chains {
+        let _ = writeln!(
+            out,
+            "kyotod_peers_required{{network=\"{}\"}} {required}",
+            chain.network
+        );
+    }
+
+    let m = &sources.metrics;
+    {
+        let networks = m.networks.lock().unwrap();
+        counter(
+            &mut out,
+            "kyotod_rebuilds_total",
+            "Light client rebuilds since start.",
+        );
+        for (network, c) in networks.iter() {
+            let _ = writeln!(
+                out,
+                "kyotod_rebuilds_total{{network=\"{network}\"}} {}",
+                c.rebuilds
+            );
+        }
+        counter(
+            &mut out,
+            "kyotod_broadcasts_total",
+            "Transaction broadcasts by source and result.",
+        );
+        for (network, c) in networks.iter() {
+            for source in [Source::Ipc, Source::Rebroadcast] {
+                for (i, result) in ["ok", "failed"].into_iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "kyotod_broadcasts_total{{network=\"{network}\",source=\"{}\",result=\"{result}\"}} {}",
+                        source.label(),
+                        c.broadcasts[source as usize][i]
+                    );
+                }
+            }
+        }
+    }
+
+    {
+        let ipc = m.ipc.lock().unwrap();
+        counter(
+            &mut out,
+            "kyotod_ipc_requests_total",
+            "IPC calls by method.",
+        );
+        for (method, h) in ipc.iter() {
+            let _ = writeln!(
+                out,
+                "kyotod_ipc_requests_total{{method=\"{method}\"}} {}",
+                h.count
+            );
+        }
+        let name = "kyotod_ipc_request_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} IPC call latency by method.");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (method, h) in ipc.iter() {
+            for (bound, n) in BUCKETS.iter().zip(h.buckets) {
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{{method=\"{method}\",le=\"{bound}\"}} {n}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{method=\"{method}\",le=\"+Inf\"}} {}",
+                h.count
+            );
+            let _ = writeln!(out, "{name}_sum{{method=\"{method}\"}} {}", h.sum);
+            let _ = writeln!(out, "{name}_count{{method=\"{method}\"}} {}", h.count);
+        }
+    }
+
+    gauge(&mut out, "kyotod_wallets", "Loaded wallets.");
+    for (chain, _) in &chains {
+        let state = chain.state.lock().unwrap();
+        let _ = writeln!(
+            out,
+            "kyotod_wallets{{network=\"{}\"}} {}",
+            chain.network,
+            state.wallets.len()
+        );
+    }
+    if sources.balances {
+        gauge(
+            &mut out,
+            "kyotod_wallet_balance_sats",
+            "Wallet balance in satoshis by network, wallet and kind.",
+        );
+        for (chain, _) in &chains {
+            let state = chain.state.lock().unwrap();
+            for entry in state.wallets.values() {
+                let b = entry.wallet.balance();
+                for (kind, amount) in [
+                    ("confirmed", b.confirmed),
+                    ("trusted_pending", b.trusted_pending),
+                    ("untrusted_pending", b.untrusted_pending),
+                    ("immature", b.immature),
+                ] {
+                    let _ = writeln!(
+                        out,
+                        "kyotod_wallet_balance_sats{{network=\"{}\",wallet=\"{}\",kind=\"{kind}\"}} {}",
+                        chain.network,
+                        escape(&entry.name),
+                        amount.to_sat()
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+/// What a scrape asks one network's light client.
+struct ChainReading {
+    height: Option<u32>,
+    tip_time: Option<u64>,
+    peers: Option<usize>,
+}
+
+impl ChainReading {
+    async fn read(chain: &ChainSources) -> Self {
+        let requester = chain.requester.lock().unwrap().clone();
+        let Some(r) = requester else {
+            return Self {
+                height: None,
+                tip_time: None,
+                peers: None,
+            };
+        };
+        let tip = r.chain_tip().await.ok();
+        let tip_time = match &tip {
+            Some(t) => r
+                .get_header(t.height)
+                .await
+                .ok()
+                .flatten()
+                .map(|ih| u64::from(ih.header.time)),
+            None => None,
+        };
+        Self {
+            height: tip.map(|t| t.height),
+            tip_time,
+            peers: r.peer_info().await.ok().map(|p| p.len()),
+        }
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+}
+
+fn counter(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use bdk_wallet::bitcoin::Network;
+
 pub fn expand(input: &str) -> PathBuf {
     if let Some(rest) = input.strip_prefix("~/") {
         let home = std::env::var("HOME").expect("HOME is not set");
@@ -13,6 +15,7 @@ pub fn expand(input: &str) -> PathBuf {
 
 pub struct Layout {
     pub root: PathBuf,
+    socket: PathBuf,
 }
 
 impl Layout {
@@ -20,7 +23,26 @@ impl Layout {
         let root = expand(datadir);
         std::fs::create_dir_all(root.join("wallets"))?;
         std::fs::create_dir_all(root.join("data"))?;
-        Ok(Self { root })
+        let socket = root.join("node.sock");
+        Ok(Self { root, socket })
+    }
+
+    pub fn with_socket(mut self, socket: &str) -> Self {
+        self.socket = expand(socket);
+        self
+    }
+
+    /// `<datadir>/<network>/`, holding the wallets, stores and queues of an
+    /// extra network. The socket, cookies, lock and pid file are the daemon's
+    /// and stay in the datadir itself.
+    pub fn for_network(&self, network: Network) -> std::io::Result<Self> {
+        let root = self.root.join(network.to_string());
+        std::fs::create_dir_all(root.join("wallets"))?;
+        std::fs::create_dir_all(root.join("data"))?;
+        Ok(Self {
+            root,
+            socket: self.socket.clone(),
+        })
     }
 
     pub fn wallets_dir(&self) -> PathBuf {
@@ -32,6 +54,42 @@ impl Layout {
     }
 
     pub fn socket(&self) -> PathBuf {
-        self.root.join("node.sock")
+        self.socket.clone()
+    }
+
+    pub fn pid_file(&self) -> PathBuf {
+        self.root.join("node.pid")
+    }
+
+    pub fn cmdline_file(&self) -> PathBuf {
+        self.root.join("kyotod.cmdline")
+    }
+
+    pub fn lock_file(&self) -> PathBuf {
+        self.root.join("kyotod.lock")
+    }
+
+    pub fn spender_cookie(&self) -> PathBuf {
+        self.root.join("spender.cookie")
+    }
+
+    pub fn admin_cookie(&self) -> PathBuf {
+        self.root.join("admin.cookie")
+    }
+
+    pub fn pending_db(&self) -> PathBuf {
+        self.root.join("pending.sqlite")
+    }
+
+    pub fn webhooks_db(&self) -> PathBuf {
+        self.root.join("webhooks.sqlite")
+    }
+
+    pub fn remote_key(&self) -> PathBuf {
+        self.root.join("remote.key")
+    }
+
+    pub fn remote_clients(&self) -> PathBuf {
+        self.root.join("remote_clients")
     }
 }
@@ -0,0 +1,284 @@
+//! Short-lived P2P connections of our own, apart from the light client's:
+//! for broadcasts that should not go through its peers, and to ask a peer
+//! outside its set how tall the chain is.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::consensus;
+use bdk_wallet::bitcoin::hex::DisplayHex;
+use bdk_wallet::bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage};
+use bdk_wallet::bitcoin::p2p::message_network::VersionMessage;
+use bdk_wallet::bitcoin::p2p::{Address, Magic, ServiceFlags};
+use bdk_wallet::bitcoin::{Network, Transaction};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::clock::unix_now;
+use crate::random;
+
+const MAX_MESSAGE: usize = 4_000_000;
+const USER_AGENT: &str = concat!("/kyotod:", env!("CARGO_PKG_VERSION"), "/");
+const SOCKS_CONNECT: u8 = 1;
+// Tor's extension to SOCKS5 for looking up a name.
+const SOCKS_RESOLVE: u8 = 0xf0;
+
+// A few of each network's seeds. Each answers a lookup with addresses of
+// peers; it is not a peer itself. See `resolve`.
+pub fn seeds(network: Network) -> &'static [&'static str] {
+    match network {
+        Network::Bitcoin => &[
+            "seed.bitcoin.sipa.be",
+            "dnsseed.bluematt.me",
+            "seed.bitcoin.jonasschnelli.ch",
+            "seed.btc.petertodd.net",
+            "seed.bitcoin.sprovoost.nl",
+            "dnsseed.emzy.de",
+            "seed.bitcoin.wiz.biz",
+        ],
+        Network::Testnet => &[
+            "testnet-seed.bitcoin.jonasschnelli.ch",
+            "seed.tbtc.petertodd.net",
+            "seed.testnet.bitcoin.sprovoost.nl",
+            "testnet-seed.bluematt.me",
+        ],
+        Network::Testnet4 => &[
+            "seed.testnet4.bitcoin.sprovoost.nl",
+            "seed.testnet4.wiz.biz",
+        ],
+        Network::Signet => &["seed.signet.bitcoin.sprovoost.nl"],
+        _ => &[],
+    }
+}
+
+/// One of the network's seeds, picked at random.
+pub fn seed_host(network: Network) -> Option<&'static str> {
+    random::pick(seeds(network)).copied()
+}
+
+/// Look up the peers a DNS seed lists. With a proxy the lookup goes through
+/// it on a fresh circuit, using Tor's RESOLVE extension, and yields the one
+/// address Tor returns; without one it uses the system resolver.
+pub async fn resolve(
+    proxy: Option<SocketAddr>,
+    host: &str,
+    port: u16,
+) -> io::Result<Vec<SocketAddr>> {
+    match proxy {
+        Some(proxy) => {
+            let (_, ip) = socks5(proxy, SOCKS_RESOLVE, host, 0).await?;
+            let ip = ip.ok_or_else(|| invalid(format!("proxy resolved {host} to a name")))?;
+            Ok(vec![SocketAddr::new(ip, port)])
+        }
+        None => Ok(tokio::net::lookup_host((host, port)).await?.collect()),
+    }
+}
+
+/// `host`, `host:port`, `ip`, `ip:port` or `[ipv6]:port`.
+pub fn split_host(spec: &str, default_port: u16) -> Result<(String, u16), String> {
+    if let Ok(ip) = spec.parse::<IpAddr>() {
+        return Ok((ip.to_string(), default_port));
+    }
+    if let Ok(addr) = spec.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), addr.port()));
+    }
+    match spec.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("{spec}: bad port {port:?}"))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((spec.to_string(), default_port)),
+    }
+}
+
+pub struct Peer {
+    stream: TcpStream,
+    magic: Magic,
+    /// The height the peer announced in its version message.
+    pub height: u32,
+}
+
+impl Peer {
+    /// Connect through `proxy` on a fresh circuit, or directly without one,
+    /// and shake hands. Callers bound the time this takes.
+    pub async fn connect(
+        network: Network,
+        proxy: Option<SocketAddr>,
+        host: &str,
+        port: u16,
+    ) -> io::Result<Self> {
+        let stream = match proxy {
+            Some(proxy) => socks5(proxy, SOCKS_CONNECT, host, port).await?.0,
+            None => TcpStream::connect((host, port)).await?,
+        };
+        let mut peer = Self {
+            stream,
+            magic: network.magic(),
+            height: 0,
+        };
+        peer.handshake().await?;
+        Ok(peer)
+    }
+
+    /// Hand over `tx` and wait until the peer has read it.
+    pub async fn send_tx(&mut self, tx: &Transaction) -> io::Result<()> {
+        self.send(NetworkMessage::Tx(tx.clone())).await?;
+        // Peers handle messages in order, so the pong comes after the tx was read.
+        let nonce = random::bytes::<8>().map(u64::from_le_bytes)?;
+        self.send(NetworkMessage::Ping(nonce)).await?;
+        loop {
+            match self.recv().await? {
+                NetworkMessage::Pong(n) if n == nonce => return Ok(()),
+                NetworkMessage::Ping(n) => self.send(NetworkMessage::Pong(n)).await?,
+                _ => {}
+            }
+        }
+    }
+
+    async fn handshake(&mut self) -> io::Result<()> {
+        // Nothing in it points back at us: no address, no height, no services.
+        let nobody = Address::new(&SocketAddr::from(([0, 0, 0, 0], 0)), ServiceFlags::NONE);
+        let version = VersionMessage::new(
+            ServiceFlags::NONE,
+            unix_now() as i64,
+            nobody.clone(),
+            nobody,
+            random::bytes::<8>().map(u64::from_le_bytes)?,
+            USER_AGENT.to_string(),
+            0,
+        );
+        self.send(NetworkMessage::Version(version)).await?;
+        let (mut version, mut verack) = (false, false);
+        while !(version && verack) {
+            match self.recv().await? {
+                NetworkMessage::Version(v) => {
+                    version = true;
+                    self.height = v.start_height.max(0) as u32;
+                    self.send(NetworkMessage::Verack).await?;
+                }
+                NetworkMessage::Verack => verack = true,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, payload: NetworkMessage) -> io::Result<()> {
+        let raw = RawNetworkMessage::new(self.magic, payload);
+        self.stream.write_all(&consensus::serialize(&raw)).await
+    }
+
+    async fn recv(&mut self) -> io::Result<NetworkMessage> {
+        // magic, command, payload length, checksum
+        let mut buf = vec![0u8; 24];
+        self.stream.read_exact(&mut buf).await?;
+        let len = u32::from_le_bytes(buf[16..20].try_into().expect("4 bytes")) as usize;
+        if len > MAX_MESSAGE {
+            return Err(invalid(format!("peer sent a {len} byte message")));
+        }
+        buf.resize(24 + len, 0);
+        self.stream.read_exact(&mut buf[24..]).await?;
+        let raw: RawNetworkMessage = consensus::deserialize(&buf).map_err(invalid)?;
+        if *raw.magic() != self.magic {
+            return Err(invalid("peer is on another network"));
+        }
+        Ok(raw.into_payload())
+    }
+}
+
+/// A SOCKS5 request (RFC 1928) with username/password auth (RFC 1929). Tor
+/// puts streams with different credentials on different circuits, so fresh
+/// random ones give every request its own circuit and exit. Returns the
+/// stream and the address in the reply, which for RESOLVE is the answer.
+async fn socks5(
+    proxy: SocketAddr,
+    cmd: u8,
+    host: &str,
+    port: u16,
+) -> io::Result<(TcpStream, Option<IpAddr>)> {
+    let mut s = TcpStream::connect(proxy).await?;
+    s.write_all(&[5, 1, 2]).await?;
+    let mut reply = [0u8; 2];
+    s.read_exact(&mut reply).await?;
+    if reply != [5, 2] {
+        return Err(io::Error::other(
+            "proxy does not take username/password auth; stream isolation needs it",
+        ));
+    }
+    let user = random::bytes::<16>()?.to_lower_hex_string();
+    let pass = random::bytes::<16>()?.to_lower_hex_string();
+    let mut auth = vec![1, user.len() as u8];
+    auth.extend(user.as_bytes());
+    auth.push(pass.len() as u8);
+    auth.extend(pass.as_bytes());
+    s.write_all(&auth).await?;
+    s.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(io::Error::other("proxy rejected the credentials"));
+    }
+
+    let mut req = vec![5, cmd, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.push(1);
+            req.extend(ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            req.push(4);
+            req.extend(ip.octets());
+        }
+        Err(_) => {
+            // The proxy resolves names, so nothing is looked up locally.
+            let name = u8::try_from(host.len())
+                .map_err(|_| invalid(format!("host name too long: {host}")))?;
+            req.push(3);
+            req.push(name);
+            req.extend(host.as_bytes());
+        }
+    }
+    req.extend(port.to_be_bytes());
+    s.write_all(&req).await?;
+    let mut head = [0u8; 4];
+    s.read_exact(&mut head).await?;
+    if head[1] != 0 {
+        let what = if cmd == SOCKS_RESOLVE {
+            "resolve"
+        } else {
+            "connect to"
+        };
+        return Err(io::Error::other(format!(
+            "proxy could not {what} {host}:{port} (reply {})",
+            head[1]
+        )));
+    }
+    let addr = match head[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            s.read_exact(&mut ip).await?;
+            Some(IpAddr::V4(Ipv4Addr::from(ip)))
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            s.read_exact(&mut ip).await?;
+            Some(IpAddr::V6(Ipv6Addr::from(ip)))
+        }
+        3 => {
+            let mut n = [0u8; 1];
+            s.read_exact(&mut n).await?;
+            let mut name = vec![0u8; n[0] as usize];
+            s.read_exact(&mut name).await?;
+            None
+        }
+        t => return Err(invalid(format!("proxy sent address type {t}"))),
+    };
+    let mut bound_port = [0u8; 2];
+    s.read_exact(&mut bound_port).await?;
+    Ok((s, addr))
+}
+
+fn invalid(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
@@ -0,0 +1,23 @@
+//! Randomness from the operating system, for tokens, nonces and jitter.
+
+use std::io;
+
+/// `N` bytes from the OS's CSPRNG.
+pub fn bytes<const N: usize>() -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(buf)
+}
+
+/// A value in `0..n`, or 0 when `n` is 0 or the OS has no randomness to give.
+pub fn below(n: u64) -> u64 {
+    match bytes::<8>() {
+        Ok(b) if n > 0 => u64::from_le_bytes(b) % n,
+        _ => 0,
+    }
+}
+
+/// One of `items`, picked at random.
+pub fn pick<T>(items: &[T]) -> Option<&T> {
+    items.get(below(items.len() as u64) as usize)
+}
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::consensus;
+use bdk_wallet::bitcoin::{Transaction, Txid};
+use bdk_wallet::chain::ChainPosition;
+use bdk_wallet::rusqlite::{self, params, Connection};
+use tracing::{info, warn};
+
+use crate::broadcast::Broadcaster;
+use crate::clock::unix_now;
+use crate::metrics::Source;
+use crate::wallet::State;
+
+pub type PendingQueue = Arc<Mutex<Pending>>;
+
+const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy)]
+pub struct Policy {
+    pub enabled: bool,
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+    /// A transaction is tracked until it or a conflicting one is this deep,
+    /// so one a reorg drops goes out again.
+    pub confirmations: u32,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_delay_secs: 10 * 60,
+            max_delay_secs: 6 * 60 * 60,
+            confirmations: 6,
+        }
+    }
+}
+
+pub struct PendingTx {
+    pub tx: Transaction,
+    pub first_broadcast: u64,
+    pub last_broadcast: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// The height it confirmed at, while it is not yet deep enough to stop
+    /// tracking. Not persisted; the next check finds it again.
+    pub confirmed: Option<u32>,
+}
+
+/// Our own outgoing transactions that are unconfirmed or only shallowly
+/// confirmed, mirrored to a small SQLite table so the queue survives
+/// restarts.
+pub struct Pending {
+    conn: Connection,
+    policy: Policy,
+    txs: BTreeMap<Txid, PendingTx>,
+}
+
+/// Where a tracked transaction stands, with the depth of the block that
+/// confirmed it or the conflicting one.
+enum Resolution {
+    Unconfirmed,
+    Confirmed { height: u32, depth: u32 },
+    Conflicted { other: Txid, depth: u32 },
+}
+
+impl Pending {
+    pub fn open(path: &Path, policy: Policy) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS pending (
+                txid TEXT PRIMARY KEY,
+                raw BLOB NOT NULL,
+                first_broadcast INTEGER NOT NULL,
+                last_broadcast INTEGER NOT NULL,
+                attempts INTEGER NOT NULL,
+                last_error TEXT
+            )",
+        )?;
+        // Queues written before last_error was stored.
+        if conn
+            .prepare("SELECT last_error FROM pending LIMIT 0")
+            .is_err()
+        {
+            conn.execute_batch("ALTER TABLE pending ADD COLUMN last_error TEXT")?;
+        }
+        let mut txs = BTreeMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT raw, first_broadcast, last_broadcast, attempts, last_error FROM pending",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, Vec<u8>>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, u32>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?;
+            for row in rows {
+                let (raw, first, last, attempts, last_error) = row?;
+                let tx: Transaction = match consensus::deserialize(&raw) {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        warn!(target: "node", "pending queue: skipping undecodable tx: {e}");
+                        continue;
+                    }
+                };
+                txs.insert(
+                    tx.compute_txid(),
+                    PendingTx {
+                        tx,
+                        first_broadcast: first as u64,
+                        last_broadcast: last as u64,
+                        attempts,
+                        last_error,
+                        confirmed: None,
+                    },
+                );
+            }
+        }
+        Ok(Self { conn, policy, txs })
+    }
+
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    /// Exponential backoff from the last attempt: the policy's base delay,
+    /// doubling up to its maximum. `None` when rebroadcasting is disabled
+    /// or the transaction has confirmed.
+    pub fn next_broadcast(&self, p: &PendingTx) -> Option<u64> {
+        if !self.policy.enabled || p.confirmed.is_some() {
+            return None;
+        }
+        let shift = p.attempts.saturating_sub(1).min(16);
+        let delay = self
+            .policy
+            .base_delay_secs
+            .saturating_mul(1 << shift)
+            .min(self.policy.max_delay_secs);
+        Some(p.last_broadcast.saturating_add(delay))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Txid, &PendingTx)> {
+        self.txs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Start tracking a transaction that was just handed to the network.
+    /// Re-tracking a known txid leaves its schedule alone.
+    pub fn track(&mut self, tx: Transaction) {
+        let txid = tx.compute_txid();
+        if self.txs.contains_key(&txid) {
+            return;
+        }
+        let now = unix_now();
+        let entry = PendingTx {
+            tx,
+            first_broadcast: now,
+            last_broadcast: now,
+            attempts: 1,
+            last_error: None,
+            confirmed: None,
+        };
+        if let Err(e) = self.conn.execute(
+            "INSERT OR REPLACE INTO pending (txid, raw, first_broadcast, last_broadcast, attempts)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                txid.to_string(),
+                consensus::serialize(&entry.tx),
+                now as i64,
+                now as i64,
+                entry.attempts,
+            ],
+        ) {
+            warn!(target: "node", "pending queue: persist {txid}: {e}");
+        }
+        info!(target: "node", "tracking {txid} for rebroadcast");
+        self.txs.insert(txid, entry);
+    }
+
+    fn due(&self, now: u64) -> Vec<Transaction> {
+        self.txs
+            .values()
+            .filter(|p| self.next_broadcast(p).is_some_and(|t| t <= now))
+            .map(|p| p.tx.clone())
+            .collect()
+    }
+
+    fn record_attempt(&mut self, txid: Txid, now: u64, error: Option<String>) {
+        let Some(entry) = self.txs.get_mut(&txid) else {
+            return;
+        };
+        entry.attempts += 1;
+        entry.last_broadcast = now;
+        entry.last_error = error;
+        if let Err(e) = self.conn.execute(
+            "UPDATE pending SET last_broadcast = ?1, attempts = ?2, last_error = ?3
+             WHERE txid = ?4",
+            params![
+                now as i64,
+                entry.attempts,
+                entry.last_error,
+                txid.to_string()
+            ],
+        ) {
+            warn!(target: "node", "pending queue: persist {txid}: {e}");
+        }
+    }
+
+    /// Note where `txid` confirmed, or that a reorg has undone it.
+    fn confirmed(&mut self, txid: Txid, height: Option<u32>) {
+        let Some(entry) = self.txs.get_mut(&txid) else {
+            return;
+        };
+        match (entry.confirmed, height) {
+            (None, Some(h)) => {
+                info!(target: "node", "{txid} confirmed at height {h}; tracking until buried")
+            }
+            (Some(_), None) => {
+                warn!(target: "node", "{txid} is no longer confirmed; rebroadcasting again")
+            }
+            _ => {}
+        }
+        entry.confirmed = height;
+    }
+
+    fn remove(&mut self, txid: Txid) {
+        self.txs.remove(&txid);
+        if let Err(e) = self.conn.execute(
+            "DELETE FROM pending WHERE txid = ?1",
+            params![txid.to_string()],
+        ) {
+            warn!(target: "node", "pending queue: delete {txid}: {e}");
+        }
+    }
+}
+
+/// Drop queue entries that are buried under the policy's confirmations or
+/// were double-spent as deep, then rebroadcast whatever is due. Uses whichever `Requester` is current, so it keeps working
+/// across light-client rebuilds.
+pub async fn rebroadcast_loop(
+    queue: PendingQueue,
+    state: Arc<Mutex<State>>,
+    broadcaster: Broadcaster,
+) {
+    let mut tick = tokio::time::interval(TICK);
+    loop {
+        tick.tick().await;
+        {
+            let state = state.lock().unwrap();
+            let mut queue = queue.lock().unwrap();
+            let needed = queue.policy.confirmations.max(1);
+            let resolved: Vec<(Txid, Resolution)> = queue
+                .iter()
+                .map(|(txid, p)| (*txid, resolve(&state, *txid, &p.tx)))
+                .collect();
+            for (txid, resolution) in resolved {
+                match resolution {
+                    Resolution::Confirmed { depth, .. } if depth >= needed => {
+                        info!(target: "node", "{txid} is {depth} blocks deep; no longer tracking it");
+                        queue.remove(txid);
+                    }
+                    Resolution::Confirmed { height, .. } => queue.confirmed(txid, Some(height)),
+                    Resolution::Conflicted { other, depth } if depth >= needed => {
+                        warn!(target: "node", "{txid} conflicts with {other}, {depth} blocks deep; no longer tracking it");
+                        queue.remove(txid);
+                    }
+                    Resolution::Conflicted { .. } | Resolution::Unconfirmed => {
+                        queue.confirmed(txid, None)
+                    }
+                }
+            }
+        }
+        let now = unix_now();
+        let due = queue.lock().unwrap().due(now);
+        if due.is_empty() {
+            continue;
+        }
+        if !broadcaster.ready(Source::Rebroadcast) {
+            continue;
+        }
+        for tx in due {
+            let txid = tx.compute_txid();
+            let error = broadcaster
+                .send(&tx, Source::Rebroadcast, Duration::ZERO)
+                .await
+                .err();
+            queue.lock().unwrap().record_attempt(txid, now, error);
+        }
+    }
+}
+
+fn resolve(state: &State, txid: Txid, tx: &Transaction) -> Resolution {
+    let spends: HashSet<_> = tx.input.iter().map(|i| i.previous_output).collect();
+    let tip = state
+        .wallets
+        .values()
+        .map(|e| e.wallet.local_chain().tip().height())
+        .max()
+        .unwrap_or(0);
+    let depth = |height: u32| (tip + 1).saturating_sub(height);
+    for entry in state.wallets.values() {
+        if let Some(wtx) = entry.wallet.get_tx(txid) {
+            if let ChainPosition::Confirmed { anchor, .. } = wtx.chain_position {
+                let height = anchor.block_id.height;
+                return Resolution::Confirmed {
+                    height,
+                    depth: depth(height),
+                };
+            }
+        }
+        for wtx in entry.wallet.transactions() {
+            if wtx.tx_node.txid == txid {
+                continue;
+            }
+            let ChainPosition::Confirmed { anchor, .. } = wtx.chain_position else {
+                continue;
+            };
+            if wtx
+                .tx_node
+                .tx
+                .input
+                .iter()
+                .any(|i| spends.contains(&i.previous_output))
+            {
+                return Resolution::Conflicted {
+                    other: wtx.tx_node.txid,
+                    depth: depth(anchor.block_id.height),
+                };
+            }
+        }
+    }
+    Resolution::Unconfirmed
+}
@@ -0,0 +1,315 @@
+//! Encrypted, mutually pinned transport for serving IPC over TCP.
+//!
+//! Each connection runs Noise IK (`Noise_IK_25519_ChaChaPoly_BLAKE2s`, via
+//! `snow`). The client already knows the server's static key from its
+//! connection string and sends its own static key in the first message.
+//! That message's payload is sealed under a key mixed from the static-static
+//! DH, so reading it proves the client holds the private half of the key it
+//! was paired with. Afterwards each direction is a stream of Noise transport
+//! messages, each behind a 2-byte big-endian length.
+//!
+//! The plaintext side is exposed as a `DuplexStream`, so the capnp vat
+//! network runs over it exactly as it does over the unix socket.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::hex::{DisplayHex, FromHex};
+use snow::{Builder, HandshakeState, StatelessTransportState};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tracing::debug;
+
+use crate::auth::Tier;
+
+const PARAMS: &str = "Noise_IK_25519_ChaChaPoly_BLAKE2s";
+// Mixed into the handshake hash, so neither side mistakes another protocol
+// built on the same pattern for this one.
+const PROLOGUE: &[u8] = b"kyotod-remote-v2";
+const SCHEME: &str = "kyotod://";
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+/// The largest Noise message, tag included.
+const MAX_MESSAGE: usize = 65_535;
+const PIPE_BUFFER: usize = 64 * 1024;
+
+/// A Curve25519 public key, written as hex.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey([u8; KEY_LEN]);
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.to_lower_hex_string())
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <[u8; KEY_LEN]>::from_hex(s)
+            .map(Self)
+            .map_err(|e| format!("key {s:?}: {e}"))
+    }
+}
+
+pub struct Keypair {
+    secret: [u8; KEY_LEN],
+    pub public: PublicKey,
+}
+
+impl Keypair {
+    pub fn generate() -> io::Result<Self> {
+        let kp = builder().generate_keypair().map_err(noise)?;
+        let (Ok(secret), Ok(public)) = (kp.private.try_into(), kp.public.try_into()) else {
+            return Err(io::Error::other("unexpected key length"));
+        };
+        Ok(Self {
+            secret,
+            public: PublicKey(public),
+        })
+    }
+
+    /// Read the keypair at `path`, `<secret hex> <public hex>`, creating it
+    /// with mode 0600 on first use.
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let parse = || {
+                    let mut fields = text.split_whitespace();
+                    let secret = <[u8; KEY_LEN]>::from_hex(fields.next()?).ok()?;
+                    let public = PublicKey::from_str(fields.next()?).ok()?;
+                    Some(Self { secret, public })
+                };
+                parse().ok_or_else(|| invalid(format!("{}: not a key file", path.display())))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let kp = Self::generate()?;
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(path)?;
+                writeln!(file, "{} {}", kp.secret.to_lower_hex_string(), kp.public)?;
+                Ok(kp)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Client keys paired with this daemon, one `<public key hex> <tier>` per
+/// line in `remote_clients`. Re-read on every connection so pairing takes
+/// effect without a restart.
+pub fn authorized_clients(path: &Path) -> io::Result<Vec<(PublicKey, Tier)>> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pk = PublicKey::from_str(fields.next()?).ok()?;
+            let tier = Tier::from_str(fields.next()?).ok()?;
+            Some((pk, tier))
+        })
+        .collect())
+}
+
+pub fn authorize_client(path: &Path, pk: &PublicKey, tier: Tier) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)?;
+    writeln!(file, "{pk} {tier}")
+}
+
+/// Where a client finds a daemon and which key it must present:
+/// `kyotod://<host>:<port>?server=<public key hex>`. Holds nothing secret.
+pub struct ConnectString {
+    pub addr: String,
+    pub server: PublicKey,
+}
+
+impl std::fmt::Display for ConnectString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{SCHEME}{}?server={}", self.addr, self.server)
+    }
+}
+
+impl FromStr for ConnectString {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("connection string must start with {SCHEME}"))?;
+        let (addr, query) = rest
+            .split_once('?')
+            .ok_or("connection string is missing the server key")?;
+        let mut server = None;
+        for pair in query.split('&') {
+            match pair.split_once('=') {
+                Some(("server", v)) => {
+                    server = Some(PublicKey::from_str(v).map_err(|e| format!("server {e}"))?)
+                }
+                _ => return Err(format!("unexpected parameter `{pair}`")),
+            }
+        }
+        Ok(Self {
+            addr: addr.to_string(),
+            server: server.ok_or("missing server key")?,
+        })
+    }
+}
+
+/// Run the responder side of the handshake. Returns the plaintext stream,
+/// the client's key and the tier it was paired with.
+pub async fn accept<S>(
+    mut stream: S,
+    server: &Keypair,
+    clients: &[(PublicKey, Tier)],
+) -> io::Result<(DuplexStream, PublicKey, Tier)>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut hs = builder()
+        .local_private_key(&server.secret)
+        .build_responder()
+        .map_err(noise)?;
+    let msg = read_frame(&mut stream).await?;
+    let mut buf = vec![0u8; MAX_MESSAGE];
+    hs.read_message(&msg, &mut buf)
+        .map_err(|_| invalid("handshake: bad client message"))?;
+    let key = remote_key(&hs).ok_or_else(|| invalid("handshake: no client key"))?;
+    let tier = clients
+        .iter()
+        .find(|(pk, _)| *pk == key)
+        .map(|(_, t)| *t)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("unpaired client key {key}"),
+            )
+        })?;
+    let n = hs.write_message(&[], &mut buf).map_err(noise)?;
+    write_frame(&mut stream, &buf[..n]).await?;
+    let transport = hs.into_stateless_transport_mode().map_err(noise)?;
+    Ok((pipe(stream, transport), key, tier))
+}
+
+/// Run the initiator side of the handshake against a pinned server key.
+pub async fn connect<S>(
+    mut stream: S,
+    server: &PublicKey,
+    client: &Keypair,
+) -> io::Result<DuplexStream>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut hs = builder()
+        .local_private_key(&client.secret)
+        .remote_public_key(&server.0)
+        .build_initiator()
+        .map_err(noise)?;
+    let mut buf = vec![0u8; MAX_MESSAGE];
+    let n = hs.write_message(&[], &mut buf).map_err(noise)?;
+    write_frame(&mut stream, &buf[..n]).await?;
+    let msg = read_frame(&mut stream).await?;
+    hs.read_message(&msg, &mut buf)
+        .map_err(|_| invalid("handshake: server failed to prove its key"))?;
+    let transport = hs.into_stateless_transport_mode().map_err(noise)?;
+    Ok(pipe(stream, transport))
+}
+
+// Shuttle bytes between the encrypted socket and a plaintext duplex pipe.
+// Each direction counts its own nonces; either task ending tears down the
+// pipe.
+fn pipe<S>(stream: S, transport: StatelessTransportState) -> DuplexStream
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let transport = Arc::new(transport);
+    let (local, remote) = tokio::io::duplex(PIPE_BUFFER);
+    let (mut plain_rx, mut plain_tx) = tokio::io::split(remote);
+    let (mut net_rx, mut net_tx) = tokio::io::split(stream);
+
+    let sender = transport.clone();
+    tokio::spawn(async move {
+        let mut nonce = 0u64;
+        let mut plain = vec![0u8; MAX_MESSAGE - TAG_LEN];
+        let mut sealed = vec![0u8; MAX_MESSAGE];
+        loop {
+            let n = match plain_rx.read(&mut plain).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let Ok(len) = sender.write_message(nonce, &plain[..n], &mut sealed) else {
+                break;
+            };
+            nonce += 1;
+            if write_frame(&mut net_tx, &sealed[..len]).await.is_err() {
+                break;
+            }
+        }
+        let _ = net_tx.shutdown().await;
+    });
+
+    tokio::spawn(async move {
+        let mut nonce = 0u64;
+        let mut plain = vec![0u8; MAX_MESSAGE];
+        loop {
+            let Ok(sealed) = read_frame(&mut net_rx).await else {
+                break;
+            };
+            let Ok(n) = transport.read_message(nonce, &sealed, &mut plain) else {
+                debug!(target: "ipc", "remote: message failed authentication");
+                break;
+            };
+            nonce += 1;
+            if plain_tx.write_all(&plain[..n]).await.is_err() {
+                break;
+            }
+        }
+        let _ = plain_tx.shutdown().await;
+    });
+
+    local
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    r.read_exact(&mut len).await?;
+    let mut msg = vec![0u8; usize::from(u16::from_be_bytes(len))];
+    r.read_exact(&mut msg).await?;
+    Ok(msg)
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(w: &mut W, msg: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(msg.len()).map_err(|_| invalid("message too long"))?;
+    w.write_all(&len.to_be_bytes()).await?;
+    w.write_all(msg).await
+}
+
+fn builder<'a>() -> Builder<'a> {
+    Builder::new(PARAMS.parse().expect("valid noise parameters")).prologue(PROLOGUE)
+}
+
+fn remote_key(hs: &HandshakeState) -> Option<PublicKey> {
+    hs.get_remote_static()?.try_into().ok().map(PublicKey)
+}
+
+fn noise(e: snow::Error) -> io::Error {
+    io::Error::other(format!("noise: {e}"))
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
@@ -0,0 +1,262 @@
+//! Chain reorganizations, found by comparing a wallet's local chain before
+//! and after each update. The history is kept in memory for `reorgs`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use bdk_wallet::bitcoin::Txid;
+use bdk_wallet::chain::{BlockId, CheckPoint};
+
+use crate::clock::unix_now;
+
+pub type ReorgHistory = Arc<Mutex<Reorgs>>;
+
+const KEEP: usize = 100;
+
+/// Blocks a wallet had that an update replaced.
+pub struct Fork {
+    /// The highest block both chains agree on.
+    pub common: BlockId,
+    /// The replaced blocks the wallet knew about, highest first. Local chains
+    /// are sparse, so this is not every block above `common`.
+    pub disconnected: Vec<BlockId>,
+}
+
+/// `None` unless some block in `before` is missing from, or has a different
+/// hash in, `after`. A chain that only grew is not a reorg.
+pub fn detect(before: &CheckPoint, after: &CheckPoint) -> Option<Fork> {
+    let mut disconnected = Vec::new();
+    for cp in before.iter() {
+        if after
+            .get(cp.height())
+            .is_some_and(|a| a.hash() == cp.hash())
+        {
+            if disconnected.is_empty() {
+                return None;
+            }
+            return Some(Fork {
+                common: cp.block_id(),
+                disconnected,
+            });
+        }
+        disconnected.push(cp.block_id());
+    }
+    // Not even genesis matches; the wallet must belong to another network.
+    None
+}
+
+/// Confirmed transactions whose block was disconnected, and where they
+/// ended up: `to` is `None` when they are unconfirmed again (or were
+/// replaced by a conflict).
+pub fn moved(
+    fork: &Fork,
+    before: &HashMap<Txid, Option<u32>>,
+    after: &HashMap<Txid, Option<u32>>,
+) -> Vec<(Txid, u32, Option<u32>)> {
+    let mut out: Vec<_> = before
+        .iter()
+        .filter_map(|(txid, h)| {
+            let from = (*h)?;
+            let to = after.get(txid).copied().flatten();
+            (from > fork.common.height && to != Some(from)).then_some((*txid, from, to))
+        })
+        .collect();
+    out.sort_by_key(|(txid, from, _)| (*from, *txid));
+    out
+}
+
+#[derive(Clone)]
+pub struct MovedTx {
+    pub wallet: String,
+    pub txid: Txid,
+    pub from: u32,
+    pub to: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct Reorg {
+    pub time: u64,
+    pub common: BlockId,
+    pub old_tip: BlockId,
+    pub new_tip: BlockId,
+    /// Wallets that saw it, in the order they reported it.
+    pub wallets: Vec<String>,
+    pub moved: Vec<MovedTx>,
+}
+
+impl Reorg {
+    /// Blocks replaced on the old chain.
+    pub fn depth(&self) -> u32 {
+        self.old_tip.height.saturating_sub(self.common.height)
+    }
+}
+
+/// The last `KEEP` reorgs, oldest first. Every wallet reports the same fork
+/// from its own update; those reports are merged into one entry.
+#[derive(Default)]
+pub struct Reorgs {
+    entries: VecDeque<Reorg>,
+}
+
+impl Reorgs {
+    pub fn new() -> ReorgHistory {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Returns the entry and whether it is new, rather than another wallet's
+    /// view of the newest one.
+    pub fn record(
+        &mut self,
+        wallet: &str,
+        fork: &Fork,
+        new_tip: BlockId,
+        moved: Vec<MovedTx>,
+    ) -> (&Reorg, bool) {
+        let old_tip = fork.disconnected[0];
+        let same = self
+            .entries
+            .back()
+            .is_some_and(|r| r.common == fork.common && r.old_tip == old_tip);
+        if !same {
+            if self.entries.len() == KEEP {
+                self.entries.pop_front();
+            }
+            self.entries.push_back(Reorg {
+                time: unix_now(),
+                common: fork.common,
+                old_tip,
+                new_tip,
+                wallets: Vec::new(),
+                moved: Vec::new(),
+            });
+        }
+        let entry = self.entries.back_mut().expect("just pushed");
+        if !entry.wallets.iter().any(|w| w == wallet) {
+            entry.wallets.push(wallet.to_string());
+        }
+        if new_tip.height > entry.new_tip.height {
+            entry.new_tip = new_tip;
+        }
+        entry.moved.extend(moved);
+        (entry, !same)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Reorg> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Distinct hashes per height and branch.
+    fn block(height: u32, branch: u8) -> BlockId {
+        let hash = format!("{branch:02x}{height:062x}").parse().unwrap();
+        BlockId { height, hash }
+    }
+
+    fn chain(blocks: &[BlockId]) -> CheckPoint {
+        CheckPoint::from_block_ids(blocks.iter().copied()).expect("sorted and non-empty")
+    }
+
+    fn txid(n: u8) -> Txid {
+        format!("{n:064x}").parse().unwrap()
+    }
+
+    fn moved_rows(wallet: &str, moved: &[(Txid, u32, Option<u32>)]) -> Vec<MovedTx> {
+        moved
+            .iter()
+            .map(|&(txid, from, to)| MovedTx {
+                wallet: wallet.to_string(),
+                txid,
+                from,
+                to,
+            })
+            .collect()
+    }
+
+    // Blocks 0..=4 on the old branch; the update keeps 0..=2 and brings
+    // 3..=5 from another one.
+    fn fork_at_2() -> (CheckPoint, CheckPoint) {
+        let before: Vec<_> = (0..=4).map(|h| block(h, 0)).collect();
+        let mut after: Vec<_> = (0..=2).map(|h| block(h, 0)).collect();
+        after.extend((3..=5).map(|h| block(h, 1)));
+        (chain(&before), chain(&after))
+    }
+
+    #[test]
+    fn growth_is_not_a_reorg() {
+        let before: Vec<_> = (0..=3).map(|h| block(h, 0)).collect();
+        let after: Vec<_> = (0..=6).map(|h| block(h, 0)).collect();
+        assert!(detect(&chain(&before), &chain(&after)).is_none());
+    }
+
+    #[test]
+    fn detects_fork_and_moved_transactions() {
+        let (before, after) = fork_at_2();
+        let fork = detect(&before, &after).expect("reorg");
+        assert_eq!(fork.common, block(2, 0));
+        assert_eq!(fork.disconnected, vec![block(4, 0), block(3, 0)]);
+
+        let heights_before = HashMap::from([
+            (txid(1), Some(3)),
+            (txid(2), Some(4)),
+            (txid(3), Some(1)),
+            (txid(4), None),
+        ]);
+        let heights_after = HashMap::from([
+            (txid(1), Some(5)),
+            (txid(2), None),
+            (txid(3), Some(1)),
+            (txid(4), None),
+        ]);
+        let moved = moved(&fork, &heights_before, &heights_after);
+        assert_eq!(moved, vec![(txid(1), 3, Some(5)), (txid(2), 4, None)]);
+
+        let mut reorgs = Reorgs::default();
+        let (r, new) = reorgs.record("a", &fork, after.block_id(), moved_rows("a", &moved));
+        assert!(new);
+        assert_eq!(r.depth(), 2);
+        assert_eq!(r.common, block(2, 0));
+        assert_eq!(r.old_tip, block(4, 0));
+        assert_eq!(r.new_tip, block(5, 1));
+        let txids: Vec<_> = r.moved.iter().map(|m| m.txid).collect();
+        assert_eq!(txids, vec![txid(1), txid(2)]);
+    }
+
+    #[test]
+    fn wallets_reporting_one_fork_share_an_entry() {
+        let (before, after) = fork_at_2();
+        let fork = detect(&before, &after).expect("reorg");
+        let mut reorgs = Reorgs::default();
+        reorgs.record("a", &fork, after.block_id(), Vec::new());
+
+        // The second wallet's update arrived a block later.
+        let later = after.push(block(6, 1)).expect("taller");
+        let moved = [(txid(7), 3, None)];
+        let (r, new) = reorgs.record("b", &fork, later.block_id(), moved_rows("b", &moved));
+        assert!(!new);
+        assert_eq!(r.wallets, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(r.new_tip, block(6, 1));
+        assert_eq!(r.moved.len(), 1);
+        assert_eq!(reorgs.len(), 1);
+
+        // A later, different fork is a new entry.
+        let deeper = Fork {
+            common: block(1, 0),
+            disconnected: vec![block(6, 1)],
+        };
+        let (_, new) = reorgs.record("a", &deeper, block(7, 2), Vec::new());
+        assert!(new);
+        assert_eq!(reorgs.len(), 2);
+    }
+}
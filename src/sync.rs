@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use bdk_kyoto::bip157::Socks5Proxy;
 use bdk_kyoto::builder::{Builder, BuilderExt};
@@ -13,18 +14,213 @@ pub type ProgressSlot = Arc<Mutex<Option<f32>>>;
 pub type RequiredPeers = Arc<Mutex<u8>>;
 pub type TrustedPeers = Arc<Mutex<Vec<SocketAddr>>>;
 pub type TorProxy = Arc<Mutex<Option<SocketAddr>>>;
-use bdk_wallet::bitcoin::Network;
-use bdk_wallet::chain::{DescriptorExt, DescriptorId};
-use bdk_wallet::KeychainKind;
+pub type SyncHealth = Arc<Mutex<Health>>;
+pub type SyncDetails = Arc<Mutex<SyncDetail>>;
+use bdk_wallet::bitcoin::{Network, Txid};
+use bdk_wallet::chain::{BlockId, DescriptorExt, DescriptorId};
+use bdk_wallet::{KeychainKind, Wallet};
 use tokio::task::JoinHandle;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use crate::wallet::State;
+use crate::clock::unix_now;
+use crate::fees::{self, FeeCache};
+use crate::hooks::Hooks;
+use crate::ipc::RequesterSlot;
+use crate::peer::{self, Peer};
+use crate::random;
+use crate::reorg::{self, Fork, MovedTx, ReorgHistory};
+use crate::wallet::{self, State};
+use crate::webhooks::WebhookQueue;
+
+pub fn default_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 8333,
+        Network::Testnet => 18333,
+        Network::Testnet4 => 48333,
+        Network::Signet => 38333,
+        Network::Regtest => 18444,
+        _ => 8333,
+    }
+}
+
+/// Resolve `ip[:port]` or `host[:port]` to a socket address, using the
+/// network's default port when none is given.
+pub fn resolve_peer(spec: &str, network: Network) -> std::io::Result<SocketAddr> {
+    use std::net::{IpAddr, ToSocketAddrs};
+    if let Ok(ip) = spec.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port(network)));
+    }
+    if let Ok(addr) = spec.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let with_port = if spec.contains(':') {
+        spec.to_string()
+    } else {
+        format!("{spec}:{}", default_port(network))
+    };
+    with_port.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{spec}: no addresses"),
+        )
+    })
+}
+
+const WARNINGS_KEPT: usize = 20;
+/// Progress older than this doesn't count toward the ETA.
+const ETA_WINDOW: Duration = Duration::from_secs(300);
+/// How often to ask a peer outside the light client's set for its height.
+const PEER_TIP_INTERVAL: Duration = Duration::from_secs(600);
+const PEER_TIP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Counts from the latest `Info::Progress`.
+#[derive(Clone, Copy)]
+pub struct Stages {
+    pub filter_headers: u32,
+    pub filters: u32,
+    /// What both counts are heading for.
+    pub total: u32,
+}
+
+/// What the light client reports besides the overall percentage.
+#[derive(Default)]
+pub struct SyncDetail {
+    /// `None` until the running light client reports progress.
+    pub stages: Option<Stages>,
+    /// Blocks downloaded since the light client started.
+    pub blocks: u64,
+    /// Unix time and text of recent warnings, newest last. Kept across
+    /// rebuilds, since they often explain one.
+    pub warnings: VecDeque<(u64, String)>,
+    /// Height one peer outside the light client's set announced, and the
+    /// unix time it did. Kept across rebuilds.
+    pub peer_tip: Option<(u32, u64)>,
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl SyncDetail {
+    /// Forget the previous light client's progress.
+    pub fn restart(&mut self) {
+        self.stages = None;
+        self.blocks = 0;
+        self.samples.clear();
+    }
+
+    /// Time left at the rate the percentage rose over the last few minutes.
+    pub fn eta(&self) -> Option<Duration> {
+        let (t0, p0) = self.samples.front()?;
+        let (t1, p1) = self.samples.back()?;
+        let secs = t1.duration_since(*t0).as_secs_f32();
+        let rate = (p1 - p0) / secs;
+        if secs < 10.0 || rate.is_nan() || rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f32((100.0 - p1).max(0.0) / rate))
+    }
+
+    fn progress(&mut self, stages: Stages, percent: f32) {
+        let now = Instant::now();
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > ETA_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, percent));
+        self.stages = Some(stages);
+    }
+
+    fn warning(&mut self, message: String) {
+        if self.warnings.len() == WARNINGS_KEPT {
+            self.warnings.pop_front();
+        }
+        self.warnings.push_back((unix_now(), message));
+    }
+}
+
+/// While the light client runs, ask a peer it is not connected to (a DNS
+/// seed's, else the first trusted one) how tall its chain is. That peer's
+/// word is the only check on how far behind our own peers might be. Only
+/// asks through the proxy, so the extra connection never leaves from our
+/// own address.
+pub async fn peer_tip_loop(
+    network: Network,
+    requester: RequesterSlot,
+    details: SyncDetails,
+    trusted: TrustedPeers,
+    proxy: TorProxy,
+) {
+    let mut tick = tokio::time::interval(PEER_TIP_INTERVAL);
+    loop {
+        tick.tick().await;
+        if requester.lock().unwrap().is_none() {
+            continue;
+        }
+        let Some(proxy) = *proxy.lock().unwrap() else {
+            debug!(target: "node", "peer tip: no proxy set; not asking");
+            continue;
+        };
+        let addr = match peer::seed_host(network) {
+            Some(seed) => {
+                let lookup = peer::resolve(Some(proxy), seed, default_port(network));
+                match tokio::time::timeout(PEER_TIP_TIMEOUT, lookup).await {
+                    Ok(Ok(addrs)) => random::pick(&addrs).copied(),
+                    Ok(Err(e)) => {
+                        debug!(target: "node", "peer tip: resolve {seed}: {e}");
+                        None
+                    }
+                    Err(_) => {
+                        debug!(target: "node", "peer tip: resolve {seed}: timed out");
+                        None
+                    }
+                }
+            }
+            None => trusted.lock().unwrap().first().copied(),
+        };
+        let Some(addr) = addr else {
+            continue;
+        };
+        let connect = Peer::connect(network, Some(proxy), &addr.ip().to_string(), addr.port());
+        match tokio::time::timeout(PEER_TIP_TIMEOUT, connect).await {
+            Ok(Ok(peer)) => {
+                details.lock().unwrap().peer_tip = Some((peer.height, unix_now()));
+            }
+            Ok(Err(e)) => debug!(target: "node", "peer tip from {addr}: {e}"),
+            Err(_) => debug!(target: "node", "peer tip from {addr}: timed out"),
+        }
+    }
+}
+
+/// Set while the light client is down after a failure, until a restart
+/// has stayed up for a while.
+#[derive(Clone, Default)]
+pub struct Health {
+    pub degraded: Option<String>,
+    /// Restarts after failures since the daemon started.
+    pub restarts: u32,
+}
 
 pub struct SyncHandle {
     pub requester: Requester,
     log_task: JoinHandle<()>,
     update_task: JoinHandle<()>,
+    fee_task: JoinHandle<()>,
+}
+
+impl SyncHandle {
+    /// Why the light client stopped working, if it did. The node holds the
+    /// sending side of the info and warning channels, so the log forwarder
+    /// only finishes once the node has exited.
+    pub fn failure(&self) -> Option<&'static str> {
+        if self.log_task.is_finished() {
+            Some("light client node exited")
+        } else if self.update_task.is_finished() {
+            Some("wallet update task exited")
+        } else {
+            None
+        }
+    }
 }
 
 pub fn spawn(
@@ -32,12 +228,23 @@ pub fn spawn(
     state: Arc<Mutex<State>>,
     scan_overrides: HashMap<String, ScanType>,
     progress: ProgressSlot,
+    details: SyncDetails,
     required_peers: RequiredPeers,
     trusted_peers: TrustedPeers,
     tor_proxy: TorProxy,
+    fees: FeeCache,
+    hooks: Hooks,
+    webhooks: WebhookQueue,
+    reorgs: ReorgHistory,
 ) -> SyncHandle {
     let client = {
-        let guard = state.lock().unwrap();
+        let mut guard = state.lock().unwrap();
+        for w in guard.wallets.values_mut() {
+            w.recovery_from = match scan_overrides.get(&w.name) {
+                Some(ScanType::Recovery { checkpoint, .. }) => Some(checkpoint.height),
+                _ => None,
+            };
+        }
         let wallets: Vec<_> = guard
             .wallets
             .values()
@@ -76,15 +283,24 @@ pub fn spawn(
 
     let log_task = tokio::spawn(forward_logs(
         progress,
+        details,
         logging.info_subscriber,
         logging.warning_subscriber,
     ));
-    let update_task = tokio::spawn(apply_updates(update_subscriber, state));
+    let update_task = tokio::spawn(apply_updates(
+        update_subscriber,
+        state,
+        hooks,
+        webhooks,
+        reorgs,
+    ));
+    let fee_task = tokio::spawn(fees::refresh_loop(network, requester.clone(), fees));
 
     SyncHandle {
         requester,
         log_task,
         update_task,
+        fee_task,
     }
 }
 
@@ -94,10 +310,12 @@ pub async fn shutdown(handle: SyncHandle) {
     }
     handle.update_task.abort();
     handle.log_task.abort();
+    handle.fee_task.abort();
 }
 
 async fn forward_logs(
     progress: ProgressSlot,
+    details: SyncDetails,
     mut info_rx: Receiver<Info>,
     mut warn_rx: UnboundedReceiver<Warning>,
 ) {
@@ -108,8 +326,19 @@ async fn forward_logs(
         tokio::select! {
             i = info_rx.recv(), if info_open => match i {
                 Some(msg) => {
-                    if let Info::Progress(p) = &msg {
-                        *progress.lock().unwrap() = Some(p.percentage_complete());
+                    match &msg {
+                        Info::Progress(p) => {
+                            let percent = p.percentage_complete();
+                            *progress.lock().unwrap() = Some(percent);
+                            let stages = Stages {
+                                filter_headers: p.filter_headers,
+                                filters: p.filters,
+                                total: p.total_to_check,
+                            };
+                            details.lock().unwrap().progress(stages, percent);
+                        }
+                        Info::BlockReceived(_) => details.lock().unwrap().blocks += 1,
+                        _ => {}
                     }
                     info!(target: "node", "{msg}");
                 }
@@ -119,7 +348,10 @@ async fn forward_logs(
                 }
             },
             w = warn_rx.recv(), if warn_open => match w {
-                Some(msg) => warn!(target: "node", "{msg}"),
+                Some(msg) => {
+                    details.lock().unwrap().warning(msg.to_string());
+                    warn!(target: "node", "{msg}");
+                }
                 None => {
                     info!(target: "node", "warning channel closed");
                     warn_open = false;
@@ -133,6 +365,9 @@ async fn forward_logs(
 async fn apply_updates(
     mut subscriber: UpdateSubscriber<wallets::Multiple>,
     state: Arc<Mutex<State>>,
+    hooks: Hooks,
+    webhooks: WebhookQueue,
+    reorgs: ReorgHistory,
 ) {
     info!(target: "node", "update task started; waiting for sync to tip");
     loop {
@@ -145,13 +380,20 @@ async fn apply_updates(
         };
         let mut state = state.lock().unwrap();
         for (desc_id, update) in updates {
-            apply_one(&mut state, desc_id, update);
+            apply_one(&mut state, desc_id, update, &hooks, &webhooks, &reorgs);
         }
     }
     info!(target: "node", "update task exiting");
 }
 
-fn apply_one(state: &mut State, desc_id: DescriptorId, update: Update) {
+fn apply_one(
+    state: &mut State,
+    desc_id: DescriptorId,
+    update: Update,
+    hooks: &Hooks,
+    webhooks: &WebhookQueue,
+    reorgs: &ReorgHistory,
+) {
     let Some(entry) = state.wallets.values_mut().find(|e| {
         e.wallet
             .public_descriptor(KeychainKind::External)
@@ -161,10 +403,39 @@ fn apply_one(state: &mut State, desc_id: DescriptorId, update: Update) {
         warn!(target: "wallet", "received update for unknown descriptor {desc_id:?}");
         return;
     };
+    let before = wallet::tx_heights(&entry.wallet);
+    let chain_before = entry.wallet.local_chain().tip();
+    let tip_before = chain_before.height();
     if let Err(e) = entry.wallet.apply_update(update) {
         error!(target: "wallet", "wallet '{}' apply: {e}", entry.name);
         return;
     }
+    entry.last_update = Some(unix_now());
+    let tip = entry.wallet.local_chain().tip();
+    if let Some(fork) = reorg::detect(&chain_before, &tip) {
+        report_reorg(
+            reorgs,
+            &entry.name,
+            &fork,
+            tip.block_id(),
+            &before,
+            &entry.wallet,
+        );
+    }
+    // Announce transactions that are new to the wallet or just confirmed.
+    for (txid, height) in wallet::tx_heights(&entry.wallet) {
+        let known = before.get(&txid);
+        if known.is_none() || (known == Some(&None) && height.is_some()) {
+            hooks.wallet_tx(&entry.name, txid, height);
+        }
+    }
+    if tip.height() > tip_before {
+        hooks.block(tip.height(), tip.hash());
+    }
+    webhooks
+        .lock()
+        .unwrap()
+        .wallet_update(&entry.name, &entry.wallet, &before, tip_before);
     match entry.wallet.persist(&mut entry.conn) {
         Ok(_) => info!(
             target: "wallet",
@@ -175,3 +446,162 @@ fn apply_one(state: &mut State, desc_id: DescriptorId, update: Update) {
         Err(e) => error!(target: "wallet", "wallet '{}' persist: {e}", entry.name),
     }
 }
+
+fn report_reorg(
+    reorgs: &ReorgHistory,
+    name: &str,
+    fork: &Fork,
+    new_tip: BlockId,
+    before: &HashMap<Txid, Option<u32>>,
+    wallet: &Wallet,
+) {
+    let moved = reorg::moved(fork, before, &wallet::tx_heights(wallet));
+    {
+        let mut reorgs = reorgs.lock().unwrap();
+        let rows = moved
+            .iter()
+            .map(|&(txid, from, to)| MovedTx {
+                wallet: name.to_string(),
+                txid,
+                from,
+                to,
+            })
+            .collect();
+        let (r, new) = reorgs.record(name, fork, new_tip, rows);
+        if new {
+            warn!(
+                target: "node",
+                "reorg of depth {} above height {}: tip {} {} replaced by {} {}",
+                r.depth(),
+                r.common.height,
+                r.old_tip.height,
+                r.old_tip.hash,
+                r.new_tip.height,
+                r.new_tip.hash,
+            );
+        }
+    }
+    for (txid, from, to) in moved {
+        match to {
+            Some(h) => warn!(
+                target: "wallet",
+                "wallet '{name}': {txid} moved from block {from} to block {h} by reorg"
+            ),
+            None => warn!(
+                target: "wallet",
+                "wallet '{name}': {txid} was in block {from}; unconfirmed after reorg"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bdk_wallet::chain::CheckPoint;
+    use bdk_wallet::rusqlite::Connection;
+    use bip139::WalletBackup;
+
+    use super::*;
+    use crate::reorg::Reorgs;
+    use crate::wallet::WalletEntry;
+    use crate::webhooks::Outbox;
+    use crate::{hooks, webhooks};
+
+    const XPRV: &str = "tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS";
+
+    // Distinct hashes per height and branch.
+    fn block(height: u32, branch: u8) -> BlockId {
+        let hash = format!("{branch:02x}{height:062x}").parse().unwrap();
+        BlockId { height, hash }
+    }
+
+    fn entry(name: &str) -> WalletEntry {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let wallet = Wallet::create(
+            format!("wpkh({XPRV}/84'/1'/0'/0/*)"),
+            format!("wpkh({XPRV}/84'/1'/0'/1/*)"),
+        )
+        .network(Network::Regtest)
+        .create_wallet(&mut conn)
+        .unwrap();
+        WalletEntry {
+            name: name.to_string(),
+            backup: WalletBackup::default(),
+            wallet,
+            conn,
+            recovery_from: None,
+            last_update: None,
+        }
+    }
+
+    // A chain update from the wallet's genesis block through `blocks`.
+    fn update(genesis: BlockId, blocks: impl IntoIterator<Item = BlockId>) -> Update {
+        let chain = CheckPoint::from_block_ids(std::iter::once(genesis).chain(blocks))
+            .expect("sorted and non-empty");
+        Update {
+            chain: Some(chain),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_on_another_branch_is_recorded_as_a_reorg() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let _rt = rt.enter();
+        let hooks = Hooks::spawn(hooks::Settings {
+            walletnotify: None,
+            blocknotify: None,
+            per_minute: 0,
+            timeout: Duration::ZERO,
+        });
+        let outbox = Outbox::open(
+            Path::new(":memory:"),
+            webhooks::Settings {
+                endpoint: None,
+                secret: String::new(),
+                confirmations: 1,
+                max_delay_secs: 0,
+            },
+        )
+        .unwrap();
+        let webhooks: WebhookQueue = Arc::new(Mutex::new(outbox));
+        let reorgs = Reorgs::new();
+
+        let entry = entry("a");
+        let genesis = entry.wallet.local_chain().tip().block_id();
+        let desc_id = entry
+            .wallet
+            .public_descriptor(KeychainKind::External)
+            .descriptor_id();
+        let mut state = State {
+            wallets: HashMap::from([("a".to_string(), entry)]),
+            active: None,
+        };
+        let tip = |state: &State| state.wallets["a"].wallet.local_chain().tip().block_id();
+
+        let first = update(genesis, (1..=3).map(|h| block(h, 0)));
+        apply_one(&mut state, desc_id, first, &hooks, &webhooks, &reorgs);
+        assert_eq!(tip(&state), block(3, 0));
+        assert!(reorgs.lock().unwrap().is_empty());
+
+        // Keeps block 1 and replaces 2..=3 with a taller branch.
+        let branch = std::iter::once(block(1, 0)).chain((2..=4).map(|h| block(h, 1)));
+        let second = update(genesis, branch);
+        apply_one(&mut state, desc_id, second, &hooks, &webhooks, &reorgs);
+        assert_eq!(tip(&state), block(4, 1));
+
+        let reorgs = reorgs.lock().unwrap();
+        assert_eq!(reorgs.len(), 1);
+        let r = reorgs.iter().next().unwrap();
+        assert_eq!(r.common, block(1, 0));
+        assert_eq!(r.old_tip, block(3, 0));
+        assert_eq!(r.new_tip, block(4, 1));
+        assert_eq!(r.depth(), 2);
+        assert_eq!(r.wallets, vec!["a".to_string()]);
+        assert!(r.moved.is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! Just enough of the systemd service protocol for `Type=notify` units and
+//! socket activation, without linking libsystemd. systemd only runs on Linux;
+//! on other targets there is never a unit to notify or a socket passed in.
+
+#[cfg(target_os = "linux")]
+use std::env;
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+use tracing::debug;
+
+#[cfg(target_os = "linux")]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Sends state updates to `$NOTIFY_SOCKET`. Every method is a no-op when the
+/// daemon was not started by systemd.
+pub struct Notifier {
+    socket: Option<(UnixDatagram, SocketAddr)>,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Read `NOTIFY_SOCKET` and `WATCHDOG_USEC`. Call after any fork, since
+    /// the watchdog is tied to the pid systemd started.
+    #[cfg(target_os = "linux")]
+    pub fn from_env() -> Self {
+        let socket = env::var_os("NOTIFY_SOCKET").and_then(|path| {
+            let path = path.to_string_lossy();
+            let addr = match path.strip_prefix('@') {
+                Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+                None => SocketAddr::from_pathname(path.as_ref()),
+            };
+            match (UnixDatagram::unbound(), addr) {
+                (Ok(sock), Ok(addr)) => Some((sock, addr)),
+                (Err(e), _) | (_, Err(e)) => {
+                    debug!(target: "node", "NOTIFY_SOCKET {path}: {e}");
+                    None
+                }
+            }
+        });
+        let watchdog = env::var("WATCHDOG_USEC")
+            .ok()
+            .filter(|_| pid_matches("WATCHDOG_PID", true))
+            .and_then(|usec| usec.parse().ok())
+            .filter(|usec| *usec > 0)
+            .map(Duration::from_micros);
+        Self { socket, watchdog }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn from_env() -> Self {
+        Self {
+            socket: None,
+            watchdog: None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// How often to call `watchdog`: half the configured timeout, as
+    /// sd_watchdog_enabled(3) recommends.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog.filter(|_| self.enabled()).map(|d| d / 2)
+    }
+
+    pub fn ready(&self) {
+        self.send(&format!("READY=1\nMAINPID={}", std::process::id()));
+    }
+
+    pub fn status(&self, status: &str) {
+        self.send(&format!("STATUS={}", status.replace('\n', " ")));
+    }
+
+    pub fn watchdog(&self) {
+        self.send("WATCHDOG=1");
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    fn send(&self, msg: &str) {
+        if let Some((sock, addr)) = &self.socket {
+            if let Err(e) = sock.send_to_addr(msg.as_bytes(), addr) {
+                debug!(target: "node", "sd_notify: {e}");
+            }
+        }
+    }
+}
+
+/// Take the first socket passed through `LISTEN_FDS`, if it was meant for
+/// this process. The variables are cleared so children don't inherit them.
+#[cfg(target_os = "linux")]
+pub fn take_listen_fd() -> io::Result<Option<OwnedFd>> {
+    let count: i32 = match env::var("LISTEN_FDS") {
+        Ok(n) if pid_matches("LISTEN_PID", false) => n
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "bad LISTEN_FDS"))?,
+        _ => return Ok(None),
+    };
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDNAMES");
+    if count < 1 {
+        return Ok(None);
+    }
+    if count > 1 {
+        debug!(target: "node", "LISTEN_FDS={count}; using only the first socket");
+    }
+    let fd = SD_LISTEN_FDS_START;
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn take_listen_fd() -> io::Result<Option<OwnedFd>> {
+    Ok(None)
+}
+
+// sd_watchdog_enabled takes a missing WATCHDOG_PID to mean this process;
+// sd_listen_fds ignores the sockets without LISTEN_PID.
+#[cfg(target_os = "linux")]
+fn pid_matches(var: &str, if_unset: bool) -> bool {
+    match env::var(var) {
+        Ok(p) => p.parse::<u32>().is_ok_and(|p| p == std::process::id()),
+        Err(_) => if_unset,
+    }
+}
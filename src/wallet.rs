@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use bdk_wallet::bitcoin::Network;
+use bdk_wallet::bitcoin::{Network, Txid};
+use bdk_wallet::chain::ChainPosition;
 use bdk_wallet::rusqlite::{self, Connection};
 use bdk_wallet::{KeychainKind, PersistedWallet, Wallet};
 use bip139::WalletBackup;
@@ -51,6 +52,17 @@ pub struct WalletEntry {
     pub backup: WalletBackup,
     pub wallet: PersistedWallet<Connection>,
     pub conn: Connection,
+    /// Checkpoint height the running light client started this wallet's
+    /// recovery scan at, or `None` when it is doing a normal sync.
+    pub recovery_from: Option<u32>,
+    /// Unix time the last update was applied, since the daemon started.
+    pub last_update: Option<u64>,
+}
+
+pub enum FileChange {
+    Loaded,
+    Unchanged,
+    DescriptorsChanged,
 }
 
 pub struct State {
@@ -62,17 +74,7 @@ impl State {
     pub fn load(layout: &Layout, network: Network) -> Result<Self, LoadError> {
         let mut wallets = HashMap::new();
         let mut names: Vec<String> = Vec::new();
-        for entry in fs::read_dir(layout.wallets_dir()).map_err(LoadError::Io)? {
-            let entry = entry.map_err(LoadError::Io)?;
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) != Some("json") {
-                continue;
-            }
-            let name = path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or_else(|| LoadError::Invalid(format!("bad filename {path:?}")))?
-                .to_owned();
+        for (name, path) in wallet_files(layout)? {
             let entry = load_one(&name, &path, layout, network)?;
             names.push(name.clone());
             wallets.insert(name, entry);
@@ -82,6 +84,56 @@ impl State {
         Ok(Self { wallets, active })
     }
 
+    /// Load any `wallets/*.json` that isn't loaded yet. Unlike `load`, a bad
+    /// file doesn't stop the others; each outcome is returned by name.
+    pub fn load_new(
+        &mut self,
+        layout: &Layout,
+        network: Network,
+    ) -> Result<Vec<(String, Result<(), LoadError>)>, LoadError> {
+        let mut outcomes = Vec::new();
+        for (name, path) in wallet_files(layout)? {
+            if self.wallets.contains_key(&name) {
+                continue;
+            }
+            let outcome = load_one(&name, &path, layout, network).map(|entry| {
+                self.wallets.insert(name.clone(), entry);
+                if self.active.is_none() {
+                    self.active = Some(name.clone());
+                }
+            });
+            outcomes.push((name, outcome));
+        }
+        Ok(outcomes)
+    }
+
+    /// Pick up a new or rewritten `wallets/<name>.json`. A new file is loaded
+    /// like at startup. A loaded wallet is left running; if the file now has
+    /// different descriptors that is reported, since the existing sqlite
+    /// store belongs to the old ones.
+    pub fn refresh_file(
+        &mut self,
+        name: &str,
+        layout: &Layout,
+        network: Network,
+    ) -> Result<FileChange, LoadError> {
+        let path = layout.wallets_dir().join(format!("{name}.json"));
+        let backup = read_backup(&path)?;
+        if let Some(entry) = self.wallets.get(name) {
+            return Ok(if descriptors(&entry.backup) == descriptors(&backup) {
+                FileChange::Unchanged
+            } else {
+                FileChange::DescriptorsChanged
+            });
+        }
+        let entry = build_entry(name, backup, layout, network)?;
+        self.wallets.insert(name.to_string(), entry);
+        if self.active.is_none() {
+            self.active = Some(name.to_string());
+        }
+        Ok(FileChange::Loaded)
+    }
+
     pub fn active_entry(&self) -> Option<&WalletEntry> {
         self.active.as_ref().and_then(|n| self.wallets.get(n))
     }
@@ -100,16 +152,49 @@ impl State {
     }
 }
 
+fn wallet_files(layout: &Layout) -> Result<Vec<(String, PathBuf)>, LoadError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(layout.wallets_dir()).map_err(LoadError::Io)? {
+        let path = entry.map_err(LoadError::Io)?.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| LoadError::Invalid(format!("bad filename {path:?}")))?
+            .to_owned();
+        files.push((name, path));
+    }
+    files.sort();
+    Ok(files)
+}
+
 fn load_one(
     name: &str,
     json_path: &Path,
     layout: &Layout,
     network: Network,
 ) -> Result<WalletEntry, LoadError> {
+    build_entry(name, read_backup(json_path)?, layout, network)
+}
+
+pub fn read_backup(json_path: &Path) -> Result<WalletBackup, LoadError> {
     let text = fs::read_to_string(json_path).map_err(LoadError::Io)?;
     let backup = WalletBackup::from_json(&text).map_err(LoadError::Json)?;
     backup.validate().map_err(LoadError::Json)?;
-    build_entry(name, backup, layout, network)
+    Ok(backup)
+}
+
+fn descriptors(backup: &WalletBackup) -> Option<(String, String)> {
+    let account = backup.accounts.first()?;
+    let external = account.descriptor.as_ref()?.as_descriptor()?.to_string();
+    let change = account
+        .change_descriptor
+        .as_ref()?
+        .as_descriptor()?
+        .to_string();
+    Some((external, change))
 }
 
 pub fn build_entry(
@@ -175,5 +260,21 @@ pub fn build_entry(
         backup,
         wallet,
         conn,
+        recovery_from: None,
+        last_update: None,
     })
 }
+
+/// Each transaction's confirmation height, or `None` while unconfirmed.
+pub fn tx_heights(wallet: &Wallet) -> HashMap<Txid, Option<u32>> {
+    wallet
+        .transactions()
+        .map(|t| {
+            let height = match t.chain_position {
+                ChainPosition::Confirmed { anchor, .. } => Some(anchor.block_id.height),
+                ChainPosition::Unconfirmed { .. } => None,
+            };
+            (t.tx_node.txid, height)
+        })
+        .collect()
+}
@@ -0,0 +1,242 @@
+//! Optional watcher on `wallets/` so provisioning tools can drop BIP-139
+//! files in place instead of calling `importWallet`. Uses inotify on Linux
+//! and polls modification times elsewhere.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::Network;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::paths::Layout;
+use crate::wallet::{FileChange, LoadError, State};
+
+#[cfg(target_os = "linux")]
+use inotify::Watcher;
+#[cfg(not(target_os = "linux"))]
+use poll::Watcher;
+
+// Editors and copy tools often produce several events per file; wait for
+// the directory to go quiet before loading anything.
+const SETTLE: Duration = Duration::from_secs(2);
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use bdk_kyoto::bip157::tokio::io::unix::AsyncFd;
+
+    const EVENT_BUFFER: usize = 4096;
+
+    pub struct Watcher {
+        fd: AsyncFd<OwnedFd>,
+    }
+
+    impl Watcher {
+        pub fn watch(dir: &Path) -> io::Result<Self> {
+            let raw = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if raw == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+            let path = CString::new(dir.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                fd: AsyncFd::new(fd)?,
+            })
+        }
+
+        /// Wait for events and return the names of the `.json` files they name.
+        pub async fn next(&mut self) -> io::Result<Vec<String>> {
+            let mut buf = [0u8; EVENT_BUFFER];
+            loop {
+                let mut guard = self.fd.readable().await?;
+                let n = match guard.try_io(|fd| {
+                    let n =
+                        unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                    if n == -1 {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(n as usize)
+                    }
+                }) {
+                    Ok(result) => result?,
+                    Err(_would_block) => continue,
+                };
+                return Ok(parse_events(&buf[..n]));
+            }
+        }
+    }
+
+    fn parse_events(buf: &[u8]) -> Vec<String> {
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut names = Vec::new();
+        let mut offset = 0;
+        while offset + header <= buf.len() {
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            let start = offset + header;
+            let end = (start + event.len as usize).min(buf.len());
+            offset = start + event.len as usize;
+            let raw = &buf[start..end];
+            let raw = &raw[..raw.iter().position(|b| *b == 0).unwrap_or(raw.len())];
+            names.extend(super::json_stem(Path::new(OsStr::from_bytes(raw))));
+        }
+        names
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod poll {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    use bdk_kyoto::bip157::tokio;
+
+    const INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Reports `.json` files that appeared or whose mtime moved since the
+    /// last scan. A file caught half-written fails to load and is retried
+    /// once its mtime moves again.
+    pub struct Watcher {
+        dir: PathBuf,
+        seen: HashMap<String, SystemTime>,
+    }
+
+    impl Watcher {
+        pub fn watch(dir: &Path) -> io::Result<Self> {
+            let mut watcher = Self {
+                dir: dir.to_path_buf(),
+                seen: HashMap::new(),
+            };
+            // Files already there were loaded at startup.
+            watcher.scan()?;
+            Ok(watcher)
+        }
+
+        pub async fn next(&mut self) -> io::Result<Vec<String>> {
+            loop {
+                tokio::time::sleep(INTERVAL).await;
+                let changed = self.scan()?;
+                if !changed.is_empty() {
+                    return Ok(changed);
+                }
+            }
+        }
+
+        fn scan(&mut self) -> io::Result<Vec<String>> {
+            let mut changed = Vec::new();
+            for entry in fs::read_dir(&self.dir)? {
+                let entry = entry?;
+                let Some(name) = super::json_stem(&entry.path()) else {
+                    continue;
+                };
+                let Ok(mtime) = entry.metadata().and_then(|m| m.modified()) else {
+                    continue;
+                };
+                if self.seen.insert(name.clone(), mtime) != Some(mtime) {
+                    changed.push(name);
+                }
+            }
+            Ok(changed)
+        }
+    }
+}
+
+fn json_stem(name: &Path) -> Option<String> {
+    if name.extension().and_then(|e| e.to_str()) != Some("json") {
+        return None;
+    }
+    name.file_stem()
+        .and_then(|s| s.to_str())
+        .map(str::to_string)
+}
+
+/// Watch `wallets/` for the life of the daemon. New files are loaded in
+/// batches, with one light-client rebuild per batch; bad files are logged and
+/// left for the next time they change.
+pub async fn watch_wallets(
+    layout: Arc<Layout>,
+    network: Network,
+    state: Arc<Mutex<State>>,
+    rebuild_tx: mpsc::Sender<Network>,
+) {
+    let dir = layout.wallets_dir();
+    let mut watcher = match Watcher::watch(&dir) {
+        Ok(w) => w,
+        Err(e) => {
+            error!(target: "wallet", "watch {}: {e}", dir.display());
+            return;
+        }
+    };
+    info!(target: "wallet", "watching {} for new wallets", dir.display());
+    loop {
+        let mut batch: BTreeSet<String> = match watcher.next().await {
+            Ok(names) => names.into_iter().collect(),
+            Err(e) => {
+                error!(target: "wallet", "watch {}: {e}", dir.display());
+                return;
+            }
+        };
+        while let Ok(more) = tokio::time::timeout(SETTLE, watcher.next()).await {
+            match more {
+                Ok(names) => batch.extend(names),
+                Err(e) => {
+                    error!(target: "wallet", "watch {}: {e}", dir.display());
+                    return;
+                }
+            }
+        }
+        if batch.is_empty() {
+            continue;
+        }
+        let loaded = apply_batch(&batch, &layout, network, &state);
+        if loaded > 0 {
+            info!(target: "wallet", "loaded {loaded} wallet(s) from {}; rebuilding", dir.display());
+            if rebuild_tx.send(network).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn apply_batch(
+    batch: &BTreeSet<String>,
+    layout: &Layout,
+    network: Network,
+    state: &Mutex<State>,
+) -> usize {
+    let mut state = state.lock().unwrap();
+    let mut loaded = 0;
+    for name in batch {
+        match state.refresh_file(name, layout, network) {
+            Ok(FileChange::Loaded) => loaded += 1,
+            Ok(FileChange::Unchanged) => debug!(target: "wallet", "{name}.json: no change"),
+            Ok(FileChange::DescriptorsChanged) => warn!(
+                target: "wallet",
+                "{name}.json: descriptors differ from the loaded wallet; not applied \
+                 (data/{name}.sqlite belongs to the old ones)"
+            ),
+            // Renamed or deleted before we got to it.
+            Err(LoadError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!(target: "wallet", "{name}.json: {e}; not loaded"),
+        }
+    }
+    loaded
+}
@@ -0,0 +1,468 @@
+//! Signed JSON webhooks for wallet events, delivered from a SQLite outbox so
+//! nothing is lost while the receiver is down.
+//!
+//! Each POST carries `X-Kyotod-Timestamp` and `X-Kyotod-Signature:
+//! sha256=<hex>`, the HMAC-SHA256 of `<timestamp>.<body>` under the shared
+//! secret. Delivery is at least once; receivers dedupe on the event `id`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bdk_kyoto::bip157::tokio;
+use bdk_wallet::bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bdk_wallet::bitcoin::hex::DisplayHex;
+use bdk_wallet::bitcoin::{Address, Txid};
+use bdk_wallet::rusqlite::{self, params, Connection};
+use bdk_wallet::Wallet;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use crate::clock::unix_now;
+
+pub type WebhookQueue = Arc<Mutex<Outbox>>;
+
+const TICK: Duration = Duration::from_secs(5);
+const BASE_DELAY_SECS: u64 = 5;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries attempted per tick, oldest first.
+const BATCH: usize = 32;
+
+/// A plain `http://host[:port]/path` URL. There is no TLS; point this at a
+/// local receiver or a proxy that adds it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("'{s}': only http:// URLs are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let port = |p: &str| -> Result<u16, String> {
+            p.parse().map_err(|_| format!("'{s}': bad port '{p}'"))
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(v6) => {
+                let (h, rest) = v6
+                    .split_once(']')
+                    .ok_or_else(|| format!("'{s}': unclosed '['"))?;
+                match rest.strip_prefix(':') {
+                    Some(p) => (h, port(p)?),
+                    None => (h, 80),
+                }
+            }
+            None => match authority.rsplit_once(':') {
+                Some((h, p)) => (h, port(p)?),
+                None => (authority, 80),
+            },
+        };
+        if host.is_empty() {
+            return Err(format!("'{s}': missing host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Endpoint {
+    // `host:port`, bracketing IPv6 literals.
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.path)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Events are only queued while an endpoint is set.
+    pub endpoint: Option<Endpoint>,
+    pub secret: String,
+    /// Depth at which `payment_confirmed` is sent.
+    pub confirmations: u32,
+    pub max_delay_secs: u64,
+}
+
+struct Delivery {
+    seq: i64,
+    id: String,
+    kind: String,
+    body: String,
+    attempts: u32,
+}
+
+/// Events waiting to be delivered, mirrored to SQLite like the rebroadcast
+/// queue.
+pub struct Outbox {
+    conn: Connection,
+    settings: Settings,
+    wake: Arc<Notify>,
+}
+
+impl Outbox {
+    pub fn open(path: &Path, settings: Settings) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                id TEXT NOT NULL UNIQUE,
+                kind TEXT NOT NULL,
+                body TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt INTEGER NOT NULL,
+                last_error TEXT
+            )",
+        )?;
+        Ok(Self {
+            conn,
+            settings,
+            wake: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        self.wake.notify_one();
+    }
+
+    pub fn len(&self) -> usize {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM outbox", [], |r| r.get::<_, i64>(0))
+            .map_or(0, |n| n as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Compare a wallet with its transaction heights and tip from before an
+    /// update, and queue an event for every change worth announcing.
+    pub fn wallet_update(
+        &mut self,
+        name: &str,
+        wallet: &Wallet,
+        before: &HashMap<Txid, Option<u32>>,
+        tip_before: u32,
+    ) {
+        if self.settings.endpoint.is_none() {
+            return;
+        }
+        let tip = wallet.local_chain().tip().height();
+        let depth = |height: Option<u32>, tip: u32| match height {
+            Some(h) if tip >= h => tip - h + 1,
+            _ => 0,
+        };
+        let after = crate::wallet::tx_heights(wallet);
+        for (txid, height) in &after {
+            let Some(wtx) = wallet.get_tx(*txid) else {
+                continue;
+            };
+            let (sent, received) = wallet.sent_and_received(&wtx.tx_node.tx);
+            let incoming = received > sent;
+            let was = before.get(txid).copied();
+            if incoming && was.is_none() {
+                self.queue(Event::new(
+                    "payment_received",
+                    name,
+                    wallet,
+                    *txid,
+                    *height,
+                    tip,
+                ));
+            }
+            let n = self.settings.confirmations.max(1);
+            if incoming && depth(was.flatten(), tip_before) < n && depth(*height, tip) >= n {
+                self.queue(Event::new(
+                    "payment_confirmed",
+                    name,
+                    wallet,
+                    *txid,
+                    *height,
+                    tip,
+                ));
+            }
+            if !incoming && height.is_some() && was.flatten().is_none() {
+                self.queue(Event::new(
+                    "outgoing_confirmed",
+                    name,
+                    wallet,
+                    *txid,
+                    *height,
+                    tip,
+                ));
+            }
+        }
+        for (txid, was) in before {
+            let Some(old) = was else { continue };
+            let now = after.get(txid).copied().flatten();
+            if now != Some(*old) {
+                let mut event = Event::new("reorg", name, wallet, *txid, now, tip);
+                event.previous_height = Some(*old);
+                self.queue(event);
+            }
+        }
+    }
+
+    fn queue(&mut self, event: Event) {
+        let (id, body) = event.encode();
+        match self.conn.execute(
+            "INSERT OR IGNORE INTO outbox (id, kind, body, next_attempt) VALUES (?1, ?2, ?3, ?4)",
+            params![id, event.kind, body, unix_now() as i64],
+        ) {
+            Ok(0) => {}
+            Ok(_) => {
+                debug!(target: "wallet", "webhook {} queued for {} in '{}'", event.kind, event.txid, event.wallet);
+                self.wake.notify_one();
+            }
+            Err(e) => warn!(target: "wallet", "webhook outbox: {e}"),
+        }
+    }
+
+    fn due(&self, now: u64) -> Result<Vec<Delivery>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, id, kind, body, attempts FROM outbox
+             WHERE next_attempt <= ?1 ORDER BY seq LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![now as i64, BATCH as i64], |r| {
+            Ok(Delivery {
+                seq: r.get(0)?,
+                id: r.get(1)?,
+                kind: r.get(2)?,
+                body: r.get(3)?,
+                attempts: r.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    fn delivered(&self, seq: i64) {
+        if let Err(e) = self
+            .conn
+            .execute("DELETE FROM outbox WHERE seq = ?1", params![seq])
+        {
+            warn!(target: "wallet", "webhook outbox: {e}");
+        }
+    }
+
+    // Backs off from BASE_DELAY_SECS, doubling up to max_delay_secs.
+    fn failed(&self, d: &Delivery, error: &str, now: u64) -> u64 {
+        let shift = d.attempts.min(16);
+        let delay = (BASE_DELAY_SECS << shift).min(self.settings.max_delay_secs.max(1));
+        if let Err(e) = self.conn.execute(
+            "UPDATE outbox SET attempts = attempts + 1, next_attempt = ?1, last_error = ?2
+             WHERE seq = ?3",
+            params![(now + delay) as i64, error, d.seq],
+        ) {
+            warn!(target: "wallet", "webhook outbox: {e}");
+        }
+        delay
+    }
+}
+
+struct Event {
+    kind: &'static str,
+    wallet: String,
+    txid: Txid,
+    height: Option<u32>,
+    previous_height: Option<u32>,
+    confirmations: u32,
+    received: u64,
+    sent: u64,
+    outputs: Vec<(String, u64)>,
+    network: String,
+}
+
+impl Event {
+    fn new(
+        kind: &'static str,
+        wallet_name: &str,
+        wallet: &Wallet,
+        txid: Txid,
+        height: Option<u32>,
+        tip: u32,
+    ) -> Self {
+        let (mut sent, mut received, mut outputs) = (0, 0, Vec::new());
+        if let Some(wtx) = wallet.get_tx(txid) {
+            let tx = &wtx.tx_node.tx;
+            let (s, r) = wallet.sent_and_received(tx);
+            sent = s.to_sat();
+            received = r.to_sat();
+            // Our outputs, so a receiver can match invoice addresses.
+            for out in &tx.output {
+                if wallet.is_mine(out.script_pubkey.clone()) {
+                    if let Ok(addr) = Address::from_script(&out.script_pubkey, wallet.network()) {
+                        outputs.push((addr.to_string(), out.value.to_sat()));
+                    }
+                }
+            }
+        }
+        Self {
+            kind,
+            wallet: wallet_name.to_string(),
+            txid,
+            height,
+            previous_height: None,
+            confirmations: height.filter(|h| tip >= *h).map_or(0, |h| tip - h + 1),
+            received,
+            sent,
+            outputs,
+            network: wallet.network().to_string(),
+        }
+    }
+
+    // The id only depends on what happened, so re-detecting the same event
+    // after a restart doesn't queue it twice.
+    fn encode(&self) -> (String, String) {
+        let key = format!(
+            "{}:{}:{}:{:?}:{:?}",
+            self.kind, self.wallet, self.txid, self.height, self.previous_height
+        );
+        let id = sha256::Hash::hash(key.as_bytes()).to_byte_array()[..16].to_lower_hex_string();
+        let body = json!({
+            "id": id,
+            "type": self.kind,
+            "network": self.network,
+            "wallet": self.wallet,
+            "txid": self.txid.to_string(),
+            "height": self.height,
+            "previous_height": self.previous_height,
+            "confirmations": self.confirmations,
+            "received_sats": self.received,
+            "sent_sats": self.sent,
+            "outputs": self.outputs.iter().map(|(a, v)| json!({"address": a, "sats": v})).collect::<Vec<_>>(),
+            "created": unix_now(),
+        });
+        (id, body.to_string())
+    }
+}
+
+/// Deliver queued events until the process exits. Woken early whenever an
+/// event is queued or the settings change.
+pub async fn deliver_loop(queue: WebhookQueue) {
+    let wake = queue.lock().unwrap().wake.clone();
+    let mut failing = false;
+    loop {
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = tokio::time::sleep(TICK) => {}
+        }
+        let (settings, due) = {
+            let q = queue.lock().unwrap();
+            let now = unix_now();
+            match q.due(now) {
+                Ok(d) => (q.settings.clone(), d),
+                Err(e) => {
+                    warn!(target: "wallet", "webhook outbox: {e}");
+                    continue;
+                }
+            }
+        };
+        let Some(endpoint) = settings.endpoint else {
+            continue;
+        };
+        for d in due {
+            let result = post(&endpoint, &settings.secret, &d).await;
+            let q = queue.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    q.delivered(d.seq);
+                    if failing {
+                        info!(target: "wallet", "webhook {endpoint} reachable again");
+                        failing = false;
+                    }
+                    debug!(target: "wallet", "webhook {} {} delivered", d.kind, d.id);
+                }
+                Err(e) => {
+                    let delay = q.failed(&d, &e, unix_now());
+                    // One warning per outage; the rest go to debug.
+                    if failing {
+                        debug!(target: "wallet", "webhook {} {}: {e}; retry in {delay}s", d.kind, d.id);
+                    } else {
+                        warn!(
+                            target: "wallet",
+                            "webhook {endpoint}: {e}; {} event(s) queued, retrying",
+                            q.len()
+                        );
+                        failing = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn post(endpoint: &Endpoint, secret: &str, d: &Delivery) -> Result<(), String> {
+    let timestamp = unix_now().to_string();
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(timestamp.as_bytes());
+    engine.input(b".");
+    engine.input(d.body.as_bytes());
+    let signature = hmac::Hmac::<sha256::Hash>::from_engine(engine)
+        .to_byte_array()
+        .to_lower_hex_string();
+    let request = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         User-Agent: kyotod\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         X-Kyotod-Event: {}\r\n\
+         X-Kyotod-Delivery: {}\r\n\
+         X-Kyotod-Timestamp: {timestamp}\r\n\
+         X-Kyotod-Signature: sha256={signature}\r\n\
+         Connection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.authority(),
+        d.body.len(),
+        d.kind,
+        d.id,
+        d.body
+    );
+    let exchange = async {
+        let mut stream = TcpStream::connect((endpoint.host.as_str(), endpoint.port)).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status).await?;
+        Ok::<_, std::io::Error>(status)
+    };
+    let status = tokio::time::timeout(HTTP_TIMEOUT, exchange)
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    let code = status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|c| c.parse::<u16>().ok())
+        .ok_or_else(|| format!("bad response '{}'", status.trim()))?;
+    if (200..300).contains(&code) {
+        Ok(())
+    } else {
+        Err(format!("HTTP {code}"))
+    }
+}